@@ -0,0 +1,74 @@
+use std::fs;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rust_ai::utils::files::{bundle_to_file, list_files, BundleOptions};
+use tempfile::{tempdir, TempDir};
+
+// region: --- Synthetic Trees
+
+/// A tree of `dirs` dirs of `files_per_dir` source files each (with a few non-matching files).
+fn wide_tree(dirs: usize, files_per_dir: usize) -> TempDir {
+    let tmp = tempdir().unwrap();
+    for d in 0..dirs {
+        let dir = tmp.path().join(format!("mod_{d}"));
+        fs::create_dir_all(&dir).unwrap();
+        for f in 0..files_per_dir {
+            write_source(&dir.join(format!("file_{f}.rs")), 50);
+        }
+        fs::write(dir.join("README.md"), "# Readme\n").unwrap();
+    }
+    tmp
+}
+
+/// A single chain of `depth` nested dirs, each with one source file.
+fn deep_tree(depth: usize) -> TempDir {
+    let tmp = tempdir().unwrap();
+    let mut dir = tmp.path().to_path_buf();
+    for d in 0..depth {
+        dir = dir.join(format!("level_{d}"));
+        fs::create_dir_all(&dir).unwrap();
+        write_source(&dir.join("file.rs"), 20);
+    }
+    tmp
+}
+
+fn write_source(file: &Path, lines: usize) {
+    let content: String = (0..lines).map(|i| format!("pub fn f_{i}() -> usize {{ {i} }}\n")).collect();
+    fs::write(file, content).unwrap();
+}
+
+// endregion: --- Synthetic Trees
+
+fn bench_list_files(c: &mut Criterion) {
+    let wide = wide_tree(20, 50);
+    c.bench_function("list_files wide (1000 files)", |b| {
+        b.iter(|| list_files(wide.path(), Some(&["**/*.rs"]), None, None).unwrap())
+    });
+
+    // NOTE: Depth is capped at 100 by list_files, deeper files are not listed.
+    let deep = deep_tree(90);
+    c.bench_function("list_files deep (90 levels)", |b| {
+        b.iter(|| list_files(deep.path(), Some(&["**/*.rs"]), None, None).unwrap())
+    });
+}
+
+fn bench_bundle_to_file(c: &mut Criterion) {
+    let wide = wide_tree(20, 50);
+    let files = list_files(wide.path(), Some(&["**/*.rs"]), None, None).unwrap();
+    let out = tempdir().unwrap();
+    let bundle = out.path().join("bundle.rs");
+    let options = BundleOptions::default();
+    c.bench_function("bundle_to_file wide (1000 files)", |b| {
+        b.iter(|| bundle_to_file(files.clone(), &bundle, &options).unwrap())
+    });
+
+    let deep = deep_tree(90);
+    let files = list_files(deep.path(), Some(&["**/*.rs"]), None, None).unwrap();
+    c.bench_function("bundle_to_file deep (90 levels)", |b| {
+        b.iter(|| bundle_to_file(files.clone(), &bundle, &options).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_list_files, bench_bundle_to_file);
+criterion_main!(benches);
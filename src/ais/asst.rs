@@ -1,22 +1,34 @@
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use futures::{stream, Stream, StreamExt};
+use reqwest::header::{HeaderMap, HeaderValue};
 use tokio::time::sleep;
 
-use crate::ais::msg::get_text_content;
-use crate::ais::{msg::user_msg, OaClient};
-use crate::utils::cli::{ico_check, ico_deleted_ok, ico_err, ico_uploaded, ico_uploading};
-use crate::utils::files::XFile;
-use crate::Result;
+use crate::ais::msg::{
+    get_file_citations, get_text_and_image_ids, user_msg_with_image, AssistantMessage, ImageMessage, RetrievalInfo,
+};
+use crate::ais::retry::{check_breaker, new_nonce, with_retry, with_retry_reclaim};
+use crate::ais::{check_writable, explain_error, http_client, msg::user_msg, ActiveRun, OaClient};
+use crate::utils::cli::{
+    ico_check, ico_deleted_ok, ico_err, ico_uploaded, ico_uploading, select, show_details, show_status,
+    show_timings, text_dim, Spinner,
+};
+use crate::utils::files::{ensure_dir, XFile};
+use crate::{Error, Result};
 use async_openai::types::{
-    CreateAssistantFileRequest, CreateFileRequest, CreateRunRequest, RunStatus,
+    AssistantFileObject, CreateAssistantFileRequest, CreateFileRequest, MessageObject, MessageRole, OpenAIFile, RequiredAction,
+    RunStepDetailsToolCalls, RunToolCallObject, StepDetails, SubmitToolOutputsRunRequest, ToolsOutputs,
 };
-use console::Term;
+use async_openai::error::OpenAIError;
+use console::{user_attended, Term};
 use derive_more::{Deref, Display, From};
 
 use async_openai::{
-    config::OpenAIConfig,
+    config::{Config, OpenAIConfig},
     types::{
         AssistantObject, AssistantToolsRetrieval, CreateAssistantRequest, CreateThreadRequest,
         ModifyAssistantRequest, ThreadObject,
@@ -26,11 +38,85 @@ use async_openai::{
 
 // region: --- Constants
 const DEFAULT_QUERY: &[(&str, &str)] = &[("limit", "100")];
+/// Max page size of the account files list.
+const FILES_PAGE_LIMIT: &str = "10000";
 const POLLING_DURATION_MS: u64 = 500;
+const DEFAULT_ANSWER_FETCH_RETRIES: u32 = 2;
+/// Max length (in chars) of the assistant instructions accepted by the API.
+const MAX_INSTRUCTIONS_CHARS: usize = 256_000;
+/// Image types accepted by the vision models.
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+/// Metadata key of the `new_nonce` tag of the created assistants and threads.
+const NONCE_KEY: &str = "buddy_nonce";
 // endregion: --- Constants
 
 // region: --- Types
 
+/// Per run settings, on top of the assistant ones.
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Appended to the assistant instructions for this run only.
+    pub additional_instructions: Option<String>,
+    /// Overrides the assistant model for this run only.
+    pub model: Option<String>,
+    /// The run is cancelled when not done by then.
+    pub deadline: Option<Instant>,
+    /// Max prompt tokens of the run (the run ends `incomplete` beyond).
+    pub max_prompt_tokens: Option<u32>,
+    /// Max completion tokens of the run (the answer is truncated beyond).
+    pub max_completion_tokens: Option<u32>,
+    /// Refetches of the answer not listed yet after the run completed (default 2).
+    pub answer_fetch_retries: Option<u32>,
+    /// Handlers of the function calls the run requires.
+    pub tool_handlers: ToolHandlers,
+}
+
+/// A function call required by a run, to be answered by its tool handler.
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub name: String,
+    /// The arguments as generated by the model (json, not validated).
+    pub arguments: String,
+}
+
+impl From<RunToolCallObject> for ToolCall {
+    fn from(call: RunToolCallObject) -> Self {
+        Self {
+            name: call.function.name,
+            arguments: call.function.arguments,
+        }
+    }
+}
+
+/// Answers a function call with its output.
+pub type ToolHandler = Arc<dyn Fn(ToolCall) -> Result<String> + Send + Sync>;
+
+/// The tool handlers by function name.
+#[derive(Clone, Default)]
+pub struct ToolHandlers(HashMap<String, ToolHandler>);
+
+impl ToolHandlers {
+    pub fn insert(&mut self, name: impl Into<String>, handler: ToolHandler) {
+        self.0.insert(name.into(), handler);
+    }
+
+    /// The output of the call, an error message when no handler or when it fails
+    /// (so the assistant can still answer).
+    fn answer(&self, call: ToolCall) -> String {
+        let name = call.name.clone();
+        match self.0.get(&name) {
+            Some(handler) => handler(call).unwrap_or_else(|err| format!("Error: {err}")),
+            None => format!("Error: no handler for the function '{name}'"),
+        }
+    }
+}
+
+impl std::fmt::Debug for ToolHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.keys()).finish()
+    }
+}
+
 pub struct CreateConfig {
     pub name: String,
     pub model: String,
@@ -39,52 +125,194 @@ pub struct CreateConfig {
 #[derive(Debug, From, Deref, Display)]
 pub struct AsstId(String);
 
-#[derive(Debug, From, Deref, Display, Serialize, Deserialize)]
+#[derive(Debug, Clone, From, Deref, Display, Serialize, Deserialize)]
 pub struct ThreadId(String);
 
 #[derive(Debug, From, Deref, Display)]
 pub struct FileId(String);
 
+#[derive(Debug, Clone, From, Deref, Display, Serialize, Deserialize)]
+pub struct RunId(String);
+
+/// Status of a run (see `get_run_status`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Queued,
+    InProgress,
+    RequiresAction,
+    Cancelling,
+    Cancelled,
+    Failed,
+    Completed,
+    /// Ended by the token limits, with a partial answer.
+    Incomplete,
+    Expired,
+    /// A status added to the API since.
+    #[serde(other)]
+    Unknown,
+}
+
+impl RunStatus {
+    /// True while the run is not done (queued or in progress).
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Self::Queued | Self::InProgress)
+    }
+}
+
+impl AsstId {
+    /// Masked form (e.g., `asst_...dVtd`) for the terminal output.
+    pub fn redacted(&self) -> String {
+        redact(&self.0)
+    }
+}
+
+impl ThreadId {
+    /// Masked form (e.g., `thread_...PhYK`) for the terminal output.
+    pub fn redacted(&self) -> String {
+        redact(&self.0)
+    }
+}
+
+impl FileId {
+    /// Masked form (e.g., `file-...Xy3z`) for the terminal output.
+    pub fn redacted(&self) -> String {
+        redact(&self.0)
+    }
+}
+
+/// Keeps the id prefix (up to the first `_` or `-`) and the last 4 chars.
+fn redact(id: &str) -> String {
+    let prefix_len = id.find(['_', '-']).map(|i| i + 1).unwrap_or(0);
+    let chars: Vec<char> = id[prefix_len..].chars().collect();
+    if chars.len() <= 8 {
+        return id.to_string();
+    }
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+
+    format!("{}...{}", &id[..prefix_len], suffix)
+}
+
+/// Token usage of a completed run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+impl std::ops::AddAssign<&Usage> for Usage {
+    fn add_assign(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// An item of a streamed run (see `run_thread_msg_stream`).
+#[derive(Debug)]
+pub enum RunDelta {
+    /// The next text of the answer.
+    Text(String),
+    /// The run completed, with its token usage.
+    /// `truncated` when ended by the token limits (`incomplete`), the answer being partial.
+    Completed {
+        run_id: RunId,
+        usage: Option<Usage>,
+        truncated: bool,
+    },
+}
+
 // endregion: --- Types
 
 // region: --- Asst CRUD
+/// Creates the assistant, tagged with a nonce so a retry after a lost response
+/// reclaims the created one instead of creating a duplicate.
 pub async fn create(oac: &OaClient, config: CreateConfig) -> Result<AsstId> {
+    check_writable("create the assistant")?;
     let oa_assts: Assistants<'_, OpenAIConfig> = oac.assistants();
+    let nonce = new_nonce();
 
-    let asst_obj: AssistantObject = oa_assts
-        .create(CreateAssistantRequest {
-            model: config.model,
-            name: Some(config.name),
-            tools: Some(vec![AssistantToolsRetrieval::default().into()]),
-            ..Default::default()
-        })
-        .await?;
+    let asst_obj: AssistantObject = with_retry_reclaim(
+        || {
+            let req = CreateAssistantRequest {
+                model: config.model.clone(),
+                name: Some(config.name.clone()),
+                tools: Some(vec![AssistantToolsRetrieval::default().into()]),
+                metadata: Some(nonce_metadata(&nonce)),
+                ..Default::default()
+            };
+            oac.traced("POST", "/assistants", oa_assts.create(req))
+        },
+        || first_by_nonce(oac, &nonce),
+    )
+    .await?;
 
     Ok(asst_obj.id.into())
 }
 
+async fn first_by_nonce(oac: &OaClient, nonce: &str) -> Result<Option<AssistantObject>> {
+    let oa_assts = oac.assistants();
+
+    let assts = with_retry(|| oac.traced("GET", "/assistants", oa_assts.list(DEFAULT_QUERY))).await?.data;
+
+    Ok(assts.into_iter().find(|a| has_nonce(&a.metadata, nonce)))
+}
+
+fn nonce_metadata(nonce: &str) -> HashMap<String, serde_json::Value> {
+    HashMap::from([(NONCE_KEY.to_string(), nonce.into())])
+}
+
+fn has_nonce(metadata: &Option<HashMap<String, serde_json::Value>>, nonce: &str) -> bool {
+    metadata
+        .as_ref()
+        .and_then(|m| m.get(NONCE_KEY))
+        .is_some_and(|v| v.as_str() == Some(nonce))
+}
+
 pub async fn load_or_create_asst(
     oac: &OaClient,
     config: CreateConfig,
     recreate: bool,
 ) -> Result<AsstId> {
-    let asst_obj = first_by_name(oac, &config.name).await?;
-    let mut asst_id = asst_obj.map(|o| AsstId::from(o.id));
+    // Interactive pick only when a user is attended, otherwise first match by name.
+    let mut asst_id = if !recreate && user_attended() {
+        select_asst(oac, &config.name).await?
+    } else {
+        let asst_obj = first_by_name(oac, &config.name).await?;
+        asst_obj.map(|o| AsstId::from(o.id))
+    };
 
     // -- Delete asst if recreate is true and asst_id
     if let (true, Some(asst_id_ref)) = (recreate, asst_id.as_ref()) {
-        delete(oac, asst_id_ref).await?;
+        delete(oac, asst_id_ref, false).await?;
         asst_id.take();
-        println!("{} Assistant {} deleted", ico_deleted_ok(), config.name);
+        if show_status() {
+            println!("{} Assistant {} deleted", ico_deleted_ok(), config.name);
+        }
     }
     // -- Create if needed
     if let Some(asst_id) = asst_id {
-        println!("{} Assistant {} loaded", ico_check(), config.name);
+        if show_status() {
+            println!(
+                "{} Assistant {} loaded ({})",
+                ico_check(),
+                config.name,
+                asst_id.redacted()
+            );
+        }
         Ok(asst_id)
     } else {
         let asst_name = config.name.clone();
         let asst_id = create(oac, config).await?;
-        println!("{} Assistant {} loaded", ico_check(), asst_name);
+        if show_status() {
+            println!(
+                "{} Assistant {} loaded ({})",
+                ico_check(),
+                asst_name,
+                asst_id.redacted()
+            );
+        }
         Ok(asst_id)
     }
 }
@@ -92,7 +320,7 @@ pub async fn load_or_create_asst(
 pub async fn first_by_name(oac: &OaClient, name: &str) -> Result<Option<AssistantObject>> {
     let oa_assts = oac.assistants();
 
-    let assts = oa_assts.list(DEFAULT_QUERY).await?.data;
+    let assts = with_retry(|| oac.traced("GET", "/assistants", oa_assts.list(DEFAULT_QUERY))).await?.data;
 
     let asst_obj = assts
         .into_iter()
@@ -101,65 +329,206 @@ pub async fn first_by_name(oac: &OaClient, name: &str) -> Result<Option<Assistan
     Ok(asst_obj)
 }
 
+/// Lists all the assistants of the account (id and name), page by page.
+pub async fn list_assistants(oac: &OaClient) -> Result<Vec<(AsstId, String)>> {
+    let oa_assts = oac.assistants();
+
+    let mut assts = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "100".to_string())];
+        if let Some(after) = after.take() {
+            query.push(("after", after));
+        }
+        let res = with_retry(|| oac.traced("GET", "/assistants", oa_assts.list(&query))).await?;
+        assts.extend(res.data.into_iter().map(|a| (a.id.into(), a.name.unwrap_or_default())));
+        match (res.has_more, res.last_id) {
+            (true, Some(last_id)) => after = Some(last_id),
+            _ => break,
+        }
+    }
+
+    Ok(assts)
+}
+
+/// Lets the user pick the assistant when several match `name`.
+/// Returns `None` when none matches, or the user chooses to create a new one.
+///
+/// NOTE: Only the same-name assistants are offered, the others belong to other buddies
+///       (their instructions and files would be overwritten).
+async fn select_asst(oac: &OaClient, name: &str) -> Result<Option<AsstId>> {
+    let oa_assts = oac.assistants();
+
+    let assts = with_retry(|| oac.traced("GET", "/assistants", oa_assts.list(DEFAULT_QUERY))).await?.data;
+    let candidates: Vec<_> = assts.into_iter().filter(|a| a.name.as_deref() == Some(name)).collect();
+
+    // None or exactly one match is not ambiguous, no need to ask.
+    match candidates.len() {
+        0 => return Ok(None),
+        1 => return Ok(candidates.into_iter().next().map(|a| a.id.into())),
+        _ => (),
+    }
+
+    let mut items: Vec<String> = candidates
+        .iter()
+        .map(|a| {
+            let asst_name = a.name.as_deref().unwrap_or("<no name>");
+            format!("{asst_name} - {} ({})", redact(&a.id), a.model)
+        })
+        .collect();
+    items.push(format!("Create new assistant '{name}'"));
+
+    let idx = select("Select the assistant", &items)?;
+
+    Ok(candidates.into_iter().nth(idx).map(|a| a.id.into()))
+}
+
+/// Retrieves the assistant, erroring with the auth and network errors told apart.
+pub async fn get_asst(oac: &OaClient, asst_id: &AsstId) -> Result<AssistantObject> {
+    let oa_assts = oac.assistants();
+    let asst_obj = with_retry(|| oac.traced("GET", format!("/assistants/{asst_id}"), oa_assts.retrieve(asst_id)))
+        .await
+        .map_err(|err| match err {
+            Error::OpenAI(err) => explain_error(err),
+            err => err,
+        })?;
+
+    Ok(asst_obj)
+}
+
 pub async fn upload_instructions(
     oac: &OaClient,
     asst_id: &AsstId,
     inst_content: String,
 ) -> Result<()> {
+    check_writable("upload the instructions")?;
+    // Checked here, the API only answers with an opaque 400.
+    let len = inst_content.chars().count();
+    if len > MAX_INSTRUCTIONS_CHARS {
+        return Err(format!(
+            "Instructions too long ({len} chars, max {MAX_INSTRUCTIONS_CHARS}). \
+             Move some of the content to a file bundle (retrieval) instead."
+        )
+        .into());
+    }
+
     let oa_assts = oac.assistants();
     let modif = ModifyAssistantRequest {
         instructions: Some(inst_content),
         ..Default::default()
     };
 
-    oa_assts.update(asst_id, modif).await?;
+    oac.traced("POST", format!("/assistants/{asst_id}"), oa_assts.update(asst_id, modif))
+        .await?;
 
     Ok(())
 }
 
-pub async fn delete(oac: &OaClient, asst_id: &AsstId) -> Result<()> {
+/// Deletes the assistant, and first its files unless `keep_files`.
+///
+/// NOTE: Kept files (e.g., shared with another assistant) stay in the account, and cost storage,
+///       until deleted from the dashboard.
+pub async fn delete(oac: &OaClient, asst_id: &AsstId, keep_files: bool) -> Result<()> {
+    check_writable("delete the assistant")?;
     let oa_assts = oac.assistants();
-    let oa_files = oac.files();
 
     // First delete the files associated to this assistant.
-    for file_id in get_file_hashmap(oac, asst_id).await?.into_values() {
-        let del_res = oa_files.delete(&file_id).await;
-        // Might be already deleted, that's ok for now.
-        if del_res.is_ok() {
-            println!("{} file deleted - {file_id}", ico_deleted_ok());
+    let file_ids = if keep_files {
+        Vec::new()
+    } else {
+        get_file_hashmap(oac, asst_id).await?.into_values().collect()
+    };
+    let mut not_deleted: Vec<(FileId, Error)> = Vec::new();
+    for file_id in file_ids {
+        match delete_org_file(oac, &file_id).await {
+            Ok(true) => {
+                if show_status() {
+                    println!("{} file deleted - {}", ico_deleted_ok(), file_id.redacted());
+                }
+            }
+            // Already deleted, that's ok.
+            Ok(false) => (),
+            Err(err) => not_deleted.push((file_id, err)),
         }
     }
 
     // No need to delete assistant files since we delete the assistant.
-    
-
 
     // -- Delete assistant
-    oa_assts.delete(asst_id).await?;
+    oac.traced("DELETE", format!("/assistants/{asst_id}"), oa_assts.delete(asst_id))
+        .await?;
+
+    // NOTE: The assistant is deleted anyway, the files left are reported (full ids) for a manual cleanup.
+    if !not_deleted.is_empty() {
+        println!(
+            "{} {} files of the deleted assistant could not be deleted, delete them manually:",
+            ico_err(),
+            not_deleted.len()
+        );
+        for (file_id, err) in not_deleted {
+            println!("  {file_id} - {err}");
+        }
+    }
 
     Ok(())
 }
 
+/// Deletes the org file, `false` when already deleted (not found).
+///
+/// NOTE: async-openai drops the http status of the API errors, and the not found error has no code,
+///       so the file is deleted with a raw request.
+async fn delete_org_file(oac: &OaClient, file_id: &FileId) -> Result<bool> {
+    let config = oac.config();
+    let url = config.url(&format!("/files/{file_id}"));
+    let res = oac.send_traced(http_client().delete(url).query(&config.query()).headers(config.headers()))
+        .await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    res.error_for_status()?;
+
+    Ok(true)
+}
+
 // endregion: --- Asst CRUD
 
 // region: --- Thread
 
+/// Creates the thread, retried on transient errors.
+///
+/// NOTE: The API can not list the threads, so a thread created before a lost response can not
+///       be reclaimed. It is left empty (tagged with its nonce) and the retry creates another.
 pub async fn create_thread(oac: &OaClient) -> Result<ThreadId> {
+    check_writable("create a thread")?;
     let oa_threads = oac.threads();
+    let nonce = new_nonce();
 
-    let res = oa_threads
-        .create(CreateThreadRequest {
+    let res = with_retry(|| {
+        let req = CreateThreadRequest {
+            metadata: Some(nonce_metadata(&nonce)),
             ..Default::default()
-        })
-        .await?;
+        };
+        oac.traced("POST", "/threads", oa_threads.create(req))
+    })
+    .await?;
 
     Ok(res.id.into())
 }
 
+pub async fn delete_thread(oac: &OaClient, thread_id: &ThreadId) -> Result<()> {
+    check_writable("delete the thread")?;
+    let oa_threads = oac.threads();
+
+    oac.traced("DELETE", format!("/threads/{thread_id}"), oa_threads.delete(thread_id))
+        .await?;
+
+    Ok(())
+}
+
 pub async fn get_thread(oac: &OaClient, thread_id: &ThreadId) -> Result<ThreadObject> {
     let oa_threads = oac.threads();
 
-    let thread_obj = oa_threads.retrieve(thread_id).await?;
+    let thread_obj = with_retry(|| oac.traced("GET", format!("/threads/{thread_id}"), oa_threads.retrieve(thread_id))).await?;
 
     Ok(thread_obj)
 }
@@ -169,81 +538,877 @@ pub async fn run_thread_msg(
     asst_id: &AsstId,
     thread_id: &ThreadId,
     msg: &str,
-) -> Result<String> {
-    let msg = user_msg(msg);
+    file_ids: Option<Vec<FileId>>,
+    options: &RunOptions,
+    images_dir: &Path,
+) -> Result<AssistantMessage> {
+    check_writable("run the assistant")?;
+    let mut msg = user_msg(msg);
+    // NOTE: Message files are retrievable in addition to the assistant files.
+    msg.file_ids = file_ids.map(|ids| ids.into_iter().map(|id| id.0).collect());
 
     // -- Attach message to thread
-    let _message_obj = oac.threads().messages(thread_id).create(msg).await?;
+    // NOTE: Creates are not retried (not idempotent), but still fail fast on an open breaker.
+    check_breaker()?;
+    let msgs_path = format!("/threads/{thread_id}/messages");
+    let _message_obj = oac.traced("POST", msgs_path, oac.threads().messages(thread_id).create(msg)).await?;
 
-    // -- Create a run for the thread
-    let run_request = CreateRunRequest {
-        assistant_id: asst_id.to_string(),
-        ..Default::default()
-    };
+    run_thread(oac, asst_id, thread_id, options, images_dir).await
+}
 
-    let run = oac.threads().runs(thread_id).create(run_request).await?;
+/// Like `run_thread_msg`, but returns the text deltas of the answer as they are generated,
+/// then the completed run.
+///
+/// The function calls are answered by the tool handlers, and the run is cancelled at the `deadline`.
+///
+/// NOTE: No images, nor retrieval info (see `count_retrieval_calls`).
+pub async fn run_thread_msg_stream(
+    oac: &OaClient,
+    asst_id: &AsstId,
+    thread_id: &ThreadId,
+    msg: &str,
+    file_ids: Option<Vec<FileId>>,
+    options: &RunOptions,
+) -> Result<impl Stream<Item = Result<RunDelta>>> {
+    check_writable("run the assistant")?;
+    let mut msg = user_msg(msg);
+    msg.file_ids = file_ids.map(|ids| ids.into_iter().map(|id| id.0).collect());
+
+    check_breaker()?;
+    let msgs_path = format!("/threads/{thread_id}/messages");
+    oac.traced("POST", msgs_path, oac.threads().messages(thread_id).create(msg)).await?;
+
+    let res = oac.send_traced(run_request(oac, asst_id, thread_id, options, true)?)
+        .await?
+        .error_for_status()?;
+
+    Ok(run_deltas(RunStream::new(oac, thread_id, options, res)))
+}
+
+/// Like `run_thread_msg`, with the `image` file uploaded and attached to the message
+/// (for the vision models).
+pub async fn run_thread_image_msg(
+    oac: &OaClient,
+    asst_id: &AsstId,
+    thread_id: &ThreadId,
+    msg: &str,
+    image: &Path,
+    options: &RunOptions,
+    images_dir: &Path,
+) -> Result<AssistantMessage> {
+    check_writable("run the assistant")?;
+    check_breaker()?;
+    let file_id = upload_image(oac, image).await?;
+    add_image_msg(oac, thread_id, &user_msg_with_image(msg, file_id.0)).await?;
+
+    run_thread(oac, asst_id, thread_id, options, images_dir).await
+}
+
+/// Runs the assistant on the thread, and returns its answer once completed.
+async fn run_thread(
+    oac: &OaClient,
+    asst_id: &AsstId,
+    thread_id: &ThreadId,
+    options: &RunOptions,
+    images_dir: &Path,
+) -> Result<AssistantMessage> {
+    // -- Create a run for the thread
+    let run_start = Instant::now();
+    let run_id = create_run(oac, asst_id, thread_id, options).await?;
+    let active_run = ActiveRun::start();
 
     // -- Loop to get result
-    let term = Term::stdout();
+    // NOTE: The spinner is cleared when dropped, on errors too.
+    let mut spinner = show_status().then(|| Spinner::start("thinking..."));
     loop {
-        term.write_str(">")?;
-        let run = oac.threads().runs(thread_id).retrieve(&run.id).await?;
-        term.write_str("<")?;
-
-        match run.status {
-            RunStatus::Completed => {
-                term.write_str("\n")?;
-                return get_first_thread_msg_content(oac, thread_id).await;
+        let status = get_run_status(oac, thread_id, &run_id).await?;
+        if let Some(spinner) = spinner.as_mut() {
+            spinner.tick();
+        }
+
+        // NOTE: Incomplete runs (token limits reached) still have a partial answer.
+        let truncated = match status {
+            RunStatus::Completed => false,
+            RunStatus::Incomplete => true,
+            RunStatus::RequiresAction => {
+                submit_tool_outputs(oac, thread_id, &run_id, &options.tool_handlers).await?;
+                continue;
             }
-            RunStatus::Queued | RunStatus::InProgress => (),
-            other => {
-                term.write_str("\n")?;
-                return Err(format!("ERROR WHILE RUN: {:?}", other).into());
+            status if status.is_pending() => {
+                if options.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    drop(spinner.take());
+                    // Best-effort, the run ends on its own otherwise.
+                    let _ = cancel_run(oac, thread_id, &run_id).await;
+                    return Err("Session time budget reached, run cancelled".into());
+                }
+                tokio::select! {
+                    _ = sleep(Duration::from_millis(POLLING_DURATION_MS)) => (),
+                    _ = active_run.interrupted() => {
+                        drop(spinner.take());
+                        // Best-effort, the run ends on its own otherwise.
+                        let _ = cancel_run(oac, thread_id, &run_id).await;
+                        return Err(Error::RunCancelled);
+                    }
+                }
+                continue;
             }
+            other => return Err(Error::RunFailed(format!("{other:?}"))),
+        };
+
+        drop(spinner.take());
+        if show_timings() {
+            println!("{}", text_dim(format!("(run {:.1}s)", run_start.elapsed().as_secs_f32())));
+        }
+        // The usage is only final once the run is done.
+        let usage = with_retry(|| get_run_state(oac, thread_id, &run_id)).await?.usage;
+        let retries = options.answer_fetch_retries.unwrap_or(DEFAULT_ANSWER_FETCH_RETRIES);
+        let msg = get_answer_msg(oac, thread_id, retries).await?;
+        if msg.is_none() && show_status() {
+            println!("{}", text_dim("(empty answer, no message found)".to_string()));
         }
-        sleep(Duration::from_millis(POLLING_DURATION_MS)).await;
+        let citations = msg.as_ref().map(get_file_citations).unwrap_or_default();
+        // Best-effort, only a diagnostic.
+        let retrieval = if show_details() {
+            count_retrieval_calls(oac, thread_id, &run_id)
+                .await
+                .ok()
+                .map(|calls| RetrievalInfo {
+                    calls,
+                    citations: citations.len(),
+                })
+        } else {
+            None
+        };
+        let (mut text, image_ids) = msg.map(get_text_and_image_ids).unwrap_or_default();
+        if truncated {
+            text.push_str(" [truncated]");
+        }
+        let mut images = Vec::new();
+        for image_id in image_ids {
+            images.push(download_file(oac, &image_id, images_dir, "png").await?);
+        }
+        return Ok(AssistantMessage {
+            text,
+            images,
+            run_id,
+            usage,
+            retrieval,
+            citations,
+        });
     }
 }
 
-pub async fn get_first_thread_msg_content(oac: &OaClient, thread_id: &ThreadId) -> Result<String> {
+/// Returns the number of retrieval tool calls in the steps of the run.
+pub async fn count_retrieval_calls(oac: &OaClient, thread_id: &ThreadId, run_id: &RunId) -> Result<usize> {
+    let oa_threads = oac.threads();
+    let oa_runs = oa_threads.runs(thread_id);
+    let oa_steps = oa_runs.steps(run_id);
+    let steps = with_retry(|| {
+        let steps_path = format!("/threads/{thread_id}/runs/{run_id}/steps");
+        oac.traced("GET", steps_path, oa_steps.list(DEFAULT_QUERY))
+    }).await?.data;
+
+    let calls = steps
+        .into_iter()
+        .filter_map(|step| match step.step_details {
+            StepDetails::ToolCalls(tool_calls) => Some(tool_calls.tool_calls),
+            StepDetails::MessageCreation(_) => None,
+        })
+        .flatten()
+        .filter(|call| matches!(call, RunStepDetailsToolCalls::Retrieval(_)))
+        .count();
+
+    Ok(calls)
+}
+
+/// Returns the answer of the completed run, the last message of the thread when from the assistant.
+///
+/// NOTE: Right after the run completed, the answer might not be listed yet,
+///       so it is refetched up to `retries` times before giving up (`None`, an empty answer).
+async fn get_answer_msg(oac: &OaClient, thread_id: &ThreadId, retries: u32) -> Result<Option<MessageObject>> {
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            sleep(Duration::from_millis(POLLING_DURATION_MS)).await;
+        }
+        let msg = get_first_thread_msg(oac, thread_id).await?;
+        if let Some(msg) = msg.filter(|msg| matches!(msg.role, MessageRole::Assistant)) {
+            return Ok(Some(msg));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the last message of the thread, `None` when empty.
+pub async fn get_first_thread_msg(oac: &OaClient, thread_id: &ThreadId) -> Result<Option<MessageObject>> {
     static QUERY: [(&str, &str); 1] = [("limit", "1")];
 
-    let messages = oac.threads().messages(thread_id).list(&QUERY).await?;
-    let msg = messages
-        .data
+    let oa_threads = oac.threads();
+    let oa_messages = oa_threads.messages(thread_id);
+    let messages = with_retry(|| oac.traced("GET", format!("/threads/{thread_id}/messages"), oa_messages.list(&QUERY))).await?;
+
+    Ok(messages.data.into_iter().next())
+}
+
+/// Returns all the messages of the thread, oldest first.
+pub async fn list_thread_msgs(oac: &OaClient, thread_id: &ThreadId) -> Result<Vec<MessageObject>> {
+    list_thread_msgs_after(oac, thread_id, None).await
+}
+
+/// Lists the messages of the thread after the `after` message id (all when `None`), oldest first.
+pub async fn list_thread_msgs_after(
+    oac: &OaClient,
+    thread_id: &ThreadId,
+    after: Option<String>,
+) -> Result<Vec<MessageObject>> {
+    let oa_threads = oac.threads();
+    let oa_messages = oa_threads.messages(thread_id);
+
+    let mut msgs = Vec::new();
+    let mut after = after;
+    loop {
+        let mut query = vec![("limit", "100".to_string()), ("order", "asc".to_string())];
+        if let Some(after) = after.take() {
+            query.push(("after", after));
+        }
+        let res = with_retry(|| oac.traced("GET", format!("/threads/{thread_id}/messages"), oa_messages.list(&query))).await?;
+        msgs.extend(res.data);
+        match (res.has_more, res.last_id) {
+            (true, Some(last_id)) => after = Some(last_id),
+            _ => break,
+        }
+    }
+
+    Ok(msgs)
+}
+
+/// Adds a user message to the thread (without running it).
+pub async fn add_thread_msg(oac: &OaClient, thread_id: &ThreadId, msg: &str) -> Result<()> {
+    check_writable("add a message")?;
+    let msgs_path = format!("/threads/{thread_id}/messages");
+    oac.traced("POST", msgs_path, oac.threads().messages(thread_id).create(user_msg(msg))).await?;
+
+    Ok(())
+}
+
+/// Adds the image message to the thread.
+///
+/// NOTE: `async_openai::types::CreateMessageRequest` only has a text content,
+///       and the image content parts are assistants v2 only,
+///       so the message is created with a raw v2 request.
+async fn add_image_msg(oac: &OaClient, thread_id: &ThreadId, msg: &ImageMessage) -> Result<()> {
+    check_writable("add a message")?;
+    let config = oac.config();
+    let url = config.url(&format!("/threads/{thread_id}/messages"));
+    oac.send_traced(http_client().post(url).query(&config.query()).headers(v2_headers(config)).json(msg))
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Creates a run of the assistant on the thread.
+async fn create_run(
+    oac: &OaClient,
+    asst_id: &AsstId,
+    thread_id: &ThreadId,
+    options: &RunOptions,
+) -> Result<RunId> {
+    check_writable("run the assistant")?;
+    #[derive(Deserialize)]
+    struct RunCreated {
+        id: String,
+    }
+
+    let run: RunCreated = oac.send_traced(run_request(oac, asst_id, thread_id, options, false)?)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(run.id.into())
+}
+
+/// The create run request, streamed (server-sent events) with `stream`.
+///
+/// NOTE: `async_openai::types::CreateRunRequest` does not have `additional_instructions`
+///       nor the token limits yet, and those and the streaming are assistants v2 only,
+///       so the run is created with a raw v2 request (see `v2_headers`).
+fn run_request(
+    oac: &OaClient,
+    asst_id: &AsstId,
+    thread_id: &ThreadId,
+    options: &RunOptions,
+    stream: bool,
+) -> Result<reqwest::RequestBuilder> {
+    #[derive(Serialize)]
+    struct CreateRun<'a> {
+        assistant_id: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        model: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        additional_instructions: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_prompt_tokens: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_completion_tokens: Option<u32>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        stream: bool,
+    }
+
+    let body = CreateRun {
+        assistant_id: asst_id,
+        model: options.model.as_deref(),
+        additional_instructions: options.additional_instructions.as_deref(),
+        max_prompt_tokens: options.max_prompt_tokens,
+        max_completion_tokens: options.max_completion_tokens,
+        stream,
+    };
+
+    let config = oac.config();
+    let url = config.url(&format!("/threads/{thread_id}/runs"));
+
+    Ok(http_client().post(url).query(&config.query()).headers(v2_headers(config)).json(&body))
+}
+
+/// The config headers, with the assistants v2 beta header.
+///
+/// NOTE: async-openai sends the v1 one, but the token limits, the `incomplete` status,
+///       and the streaming are v2 only, so the raw run requests are all v2.
+fn v2_headers(config: &OpenAIConfig) -> HeaderMap {
+    let mut headers = config.headers();
+    headers.insert("OpenAI-Beta", HeaderValue::from_static("assistants=v2"));
+    headers
+}
+
+/// The state of a run.
+#[derive(Debug, Deserialize)]
+struct RunState {
+    status: RunStatus,
+    usage: Option<Usage>,
+    required_action: Option<RequiredAction>,
+}
+
+/// Answers the function calls required by the run with the tool handlers, and submits their outputs.
+async fn submit_tool_outputs(
+    oac: &OaClient,
+    thread_id: &ThreadId,
+    run_id: &RunId,
+    handlers: &ToolHandlers,
+) -> Result<()> {
+    let run = with_retry(|| get_run_state(oac, thread_id, run_id)).await?;
+    // Already submitted (e.g., polled again before the run moved on).
+    let Some(required_action) = run.required_action else {
+        return Ok(());
+    };
+    let tool_outputs = answer_tool_calls(required_action, handlers);
+
+    // NOTE: Not retried (not idempotent), but still fail fast on an open breaker.
+    check_breaker()?;
+    let outputs_path = format!("/threads/{thread_id}/runs/{run_id}/submit_tool_outputs");
+    let oa_threads = oac.threads();
+    let oa_runs = oa_threads.runs(thread_id);
+    let submit = oa_runs.submit_tool_outputs(run_id, SubmitToolOutputsRunRequest { tool_outputs });
+    oac.traced("POST", outputs_path, submit).await?;
+
+    Ok(())
+}
+
+/// The outputs of the function calls required by the run, answered by the tool handlers.
+fn answer_tool_calls(required_action: RequiredAction, handlers: &ToolHandlers) -> Vec<ToolsOutputs> {
+    required_action
+        .submit_tool_outputs
+        .tool_calls
         .into_iter()
-        .next()
-        .ok_or_else(|| "No message found".to_string())?;
+        .map(|call| {
+            if show_status() {
+                println!("{}", text_dim(format!("(tool: {})", call.function.name)));
+            }
+            ToolsOutputs {
+                tool_call_id: Some(call.id.clone()),
+                output: Some(handlers.answer(call.into())),
+            }
+        })
+        .collect()
+}
 
-    let text = get_text_content(msg)?;
+/// Returns the current status of the run, e.g., for a custom polling.
+pub async fn get_run_status(oac: &OaClient, thread_id: &ThreadId, run_id: &RunId) -> Result<RunStatus> {
+    let run = with_retry(|| get_run_state(oac, thread_id, run_id)).await?;
 
-    Ok(text)
+    Ok(run.status)
+}
+
+/// Cancels the run.
+async fn cancel_run(oac: &OaClient, thread_id: &ThreadId, run_id: &str) -> Result<()> {
+    let cancel_path = format!("/threads/{thread_id}/runs/{run_id}/cancel");
+    oac.traced("POST", cancel_path, oac.threads().runs(thread_id).cancel(run_id)).await?;
+
+    Ok(())
+}
+
+/// Cancels the last run of the thread when still pending (e.g., a streamed run interrupted).
+pub async fn cancel_last_run(oac: &OaClient, thread_id: &ThreadId) -> Result<()> {
+    #[derive(Deserialize)]
+    struct RunsPage {
+        data: Vec<LastRun>,
+    }
+    #[derive(Deserialize)]
+    struct LastRun {
+        id: String,
+        status: RunStatus,
+    }
+
+    let config = oac.config();
+    let url = config.url(&format!("/threads/{thread_id}/runs"));
+    let runs: RunsPage = with_retry(|| async {
+        oac.send_traced(http_client().get(&url).query(&config.query()).query(&[("limit", "1")]).headers(v2_headers(config)))
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .map_err(OpenAIError::from)
+    })
+    .await?;
+
+    if let Some(run) = runs.data.into_iter().next().filter(|run| run.status.is_pending()) {
+        cancel_run(oac, thread_id, &run.id).await?;
+    }
+
+    Ok(())
+}
+
+/// Returns the state of a run.
+///
+/// NOTE: `async_openai::types::RunObject` does not expose `usage` nor the `incomplete` status yet,
+///       so the run is fetched with a raw request.
+async fn get_run_state(
+    oac: &OaClient,
+    thread_id: &ThreadId,
+    run_id: &RunId,
+) -> std::result::Result<RunState, OpenAIError> {
+    let config = oac.config();
+    let url = config.url(&format!("/threads/{thread_id}/runs/{run_id}"));
+    let run_state = oac.send_traced(http_client().get(url).query(&config.query()).headers(v2_headers(config)))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(run_state)
 }
 
 // endregion --- Thread
 
+// region: --- Run Stream
+
+/// A server-sent event of a streamed run, as far as the text deltas, usage, and function calls are concerned.
+enum RunEvent {
+    Created(RunId),
+    Delta(String),
+    RequiresAction(RunRequiringAction),
+    /// Completed, or `incomplete` (truncated) when `true`.
+    Completed(RunCompleted, bool),
+    Failed(String),
+    Done,
+    Other,
+}
+
+#[derive(Deserialize)]
+struct MessageDelta {
+    delta: MessageDeltaContent,
+}
+
+#[derive(Deserialize)]
+struct MessageDeltaContent {
+    #[serde(default)]
+    content: Vec<MessageDeltaPart>,
+}
+
+#[derive(Deserialize)]
+struct MessageDeltaPart {
+    text: Option<MessageDeltaText>,
+}
+
+#[derive(Deserialize)]
+struct MessageDeltaText {
+    value: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RunEnded {
+    status: RunStatus,
+    last_error: Option<RunError>,
+}
+
+#[derive(Deserialize)]
+struct RunCreated {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct RunRequiringAction {
+    id: String,
+    required_action: Option<RequiredAction>,
+}
+
+#[derive(Deserialize)]
+struct RunCompleted {
+    id: String,
+    usage: Option<Usage>,
+}
+
+/// The tool outputs of a streamed run, the run going on streamed.
+#[derive(Serialize)]
+struct SubmitToolOutputsStream {
+    tool_outputs: Vec<ToolsOutputs>,
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct RunError {
+    message: String,
+}
+
+/// The server-sent events of a streamed run, with what it takes to answer its function calls,
+/// and to cancel it at the deadline.
+struct RunStream {
+    oac: OaClient,
+    thread_id: ThreadId,
+    handlers: ToolHandlers,
+    deadline: Option<Instant>,
+    events: Pin<Box<dyn Stream<Item = reqwest::Result<Vec<u8>>> + Send>>,
+    buf: Vec<u8>,
+    /// Known once the run is created (see `cancel`).
+    run_id: Option<RunId>,
+}
+
+impl RunStream {
+    fn new(oac: &OaClient, thread_id: &ThreadId, options: &RunOptions, res: reqwest::Response) -> Self {
+        Self {
+            oac: oac.clone(),
+            thread_id: thread_id.clone(),
+            handlers: options.tool_handlers.clone(),
+            deadline: options.deadline,
+            events: sse_events(res),
+            buf: Vec::new(),
+            run_id: None,
+        }
+    }
+
+    /// The next delta of the run, `None` once done.
+    async fn next_delta(&mut self) -> Option<Result<RunDelta>> {
+        loop {
+            // Events are separated by a blank line.
+            if let Some(pos) = self.buf.windows(2).position(|w| w == b"\n\n") {
+                let event: Vec<u8> = self.buf.drain(..pos + 2).collect();
+                match parse_run_event(&String::from_utf8_lossy(&event)) {
+                    RunEvent::Created(run_id) => self.run_id = Some(run_id),
+                    RunEvent::Delta(text) => return Some(Ok(RunDelta::Text(text))),
+                    RunEvent::RequiresAction(run) => {
+                        if let Err(err) = self.submit_tool_outputs(run).await {
+                            return Some(Err(err));
+                        }
+                    }
+                    RunEvent::Completed(run, truncated) => {
+                        return Some(Ok(RunDelta::Completed {
+                            run_id: run.id.into(),
+                            usage: run.usage,
+                            truncated,
+                        }))
+                    }
+                    RunEvent::Failed(msg) => return Some(Err(Error::RunFailed(msg))),
+                    RunEvent::Done => return None,
+                    RunEvent::Other => (),
+                }
+                continue;
+            }
+
+            let next = match self.deadline {
+                Some(deadline) if Instant::now() >= deadline => return Some(Err(self.cancel().await)),
+                Some(deadline) => match tokio::time::timeout_at(deadline.into(), self.events.next()).await {
+                    Ok(next) => next,
+                    Err(_) => return Some(Err(self.cancel().await)),
+                },
+                None => self.events.next().await,
+            };
+            match next {
+                Some(Ok(bytes)) => self.buf.extend(bytes.iter().filter(|b| **b != b'\r')),
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => return None,
+            }
+        }
+    }
+
+    /// Answers the function calls with the tool handlers, the run going on in a new stream.
+    async fn submit_tool_outputs(&mut self, run: RunRequiringAction) -> Result<()> {
+        let run_id: RunId = run.id.into();
+        let tool_outputs = run
+            .required_action
+            .map(|required_action| answer_tool_calls(required_action, &self.handlers))
+            .unwrap_or_default();
+
+        // NOTE: Not retried (not idempotent), but still fail fast on an open breaker.
+        check_breaker()?;
+        let config = self.oac.config();
+        let url = config.url(&format!("/threads/{}/runs/{run_id}/submit_tool_outputs", self.thread_id));
+        let body = SubmitToolOutputsStream {
+            tool_outputs,
+            stream: true,
+        };
+        let res = self.oac.send_traced(http_client().post(url).query(&config.query()).headers(v2_headers(config)).json(&body))
+            .await?
+            .error_for_status()?;
+
+        self.events = sse_events(res);
+        self.buf.clear();
+        self.run_id = Some(run_id);
+
+        Ok(())
+    }
+
+    /// Cancels the run at the deadline, and returns the error ending the stream.
+    async fn cancel(&self) -> Error {
+        if let Some(run_id) = &self.run_id {
+            // Best-effort, the run ends on its own otherwise.
+            let _ = cancel_run(&self.oac, &self.thread_id, run_id).await;
+        }
+        "Session time budget reached, run cancelled".into()
+    }
+}
+
+fn sse_events(res: reqwest::Response) -> Pin<Box<dyn Stream<Item = reqwest::Result<Vec<u8>>> + Send>> {
+    Box::pin(res.bytes_stream().map(|bytes| bytes.map(|bytes| bytes.to_vec())))
+}
+
+/// The text deltas of the streamed run, then the completed run.
+/// Errors on a failed (or cancelled, expired, ...) run, and end there.
+fn run_deltas(run_stream: RunStream) -> impl Stream<Item = Result<RunDelta>> {
+    stream::unfold(Some(run_stream), |run_stream| async move {
+        let mut run_stream = run_stream?;
+        match run_stream.next_delta().await? {
+            Ok(delta) => Some((Ok(delta), Some(run_stream))),
+            Err(err) => Some((Err(err), None)),
+        }
+    })
+}
+
+fn parse_run_event(event: &str) -> RunEvent {
+    let mut name = "";
+    let mut data = String::new();
+    for line in event.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = value.trim();
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push_str(value.trim_start());
+        }
+    }
+
+    match name {
+        "thread.run.created" => match serde_json::from_str::<RunCreated>(&data) {
+            Ok(run) => RunEvent::Created(run.id.into()),
+            Err(_) => RunEvent::Other,
+        },
+        "thread.message.delta" => {
+            let Ok(msg_delta) = serde_json::from_str::<MessageDelta>(&data) else {
+                return RunEvent::Other;
+            };
+            let text: String = msg_delta
+                .delta
+                .content
+                .into_iter()
+                .filter_map(|part| part.text.and_then(|text| text.value))
+                .collect();
+            if text.is_empty() {
+                RunEvent::Other
+            } else {
+                RunEvent::Delta(text)
+            }
+        }
+        // NOTE: Incomplete runs (token limits reached) still have a partial answer.
+        "thread.run.completed" | "thread.run.incomplete" => match serde_json::from_str::<RunCompleted>(&data) {
+            Ok(run) => RunEvent::Completed(run, name == "thread.run.incomplete"),
+            Err(_) => RunEvent::Other,
+        },
+        "thread.run.requires_action" => match serde_json::from_str::<RunRequiringAction>(&data) {
+            Ok(run) => RunEvent::RequiresAction(run),
+            Err(err) => RunEvent::Failed(format!("Invalid required action: {err}")),
+        },
+        "thread.run.failed" | "thread.run.cancelled" | "thread.run.expired" => {
+            match serde_json::from_str::<RunEnded>(&data) {
+                Ok(RunEnded {
+                    last_error: Some(err), ..
+                }) => RunEvent::Failed(err.message),
+                Ok(run) => RunEvent::Failed(format!("{:?}", run.status)),
+                Err(_) => RunEvent::Failed(name.to_string()),
+            }
+        }
+        "error" => RunEvent::Failed(data),
+        "done" => RunEvent::Done,
+        _ => RunEvent::Other,
+    }
+}
+
+// endregion: --- Run Stream
+
 // region: --- Files
 
 /// returns the file id by file name hashmap.
 pub async fn get_file_hashmap(oac: &OaClient, asst_id: &AsstId) -> Result<HashMap<String, FileId>> {
+    // Build or file_name:file_id hashmap
+    let file_id_by_name: HashMap<String, FileId> = get_asst_org_files(oac, asst_id)
+        .await?
+        .into_iter()
+        .map(|org_file| (org_file.filename, org_file.id.into()))
+        .collect();
+
+    Ok(file_id_by_name)
+}
+
+/// Returns the org files (which have the `.filename`) attached to the assistant.
+pub async fn get_asst_org_files(oac: &OaClient, asst_id: &AsstId) -> Result<Vec<OpenAIFile>> {
     // get all asst files (files do not have .name)
-    let oa_assts = oac.assistants();
-    let oa_asst_files = oa_assts.files(asst_id);
-    let asst_files = oa_asst_files.list(DEFAULT_QUERY).await?.data;
+    let asst_files = list_asst_files(oac, asst_id).await?;
     let asst_file_ids: HashSet<String> = asst_files.into_iter().map(|f| f.id).collect();
 
     // Get all files for org (those files have .filename)
-    let oa_files = oac.files();
-    let org_files = oa_files.list().await?.data; // need changes
+    let org_files = list_org_files(oac).await?;
 
-    // Build or file_name:file_id hashmap
-    let file_id_by_name: HashMap<String, FileId> = org_files
+    let org_files = org_files
         .into_iter()
         .filter(|org_file| asst_file_ids.contains(&org_file.id))
-        .map(|org_file| (org_file.filename, org_file.id.into()))
         .collect();
 
-    Ok(file_id_by_name)
+    Ok(org_files)
+}
+
+/// Lists all the files attached to the assistant, page by page.
+async fn list_asst_files(oac: &OaClient, asst_id: &AsstId) -> Result<Vec<AssistantFileObject>> {
+    let oa_assts = oac.assistants();
+    let oa_asst_files = oa_assts.files(asst_id);
+
+    let mut files = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", "100".to_string())];
+        if let Some(after) = after.take() {
+            query.push(("after", after));
+        }
+        let res = with_retry(|| oac.traced("GET", format!("/assistants/{asst_id}/files"), oa_asst_files.list(&query)))
+            .await?;
+        files.extend(res.data);
+        match (res.has_more, res.last_id) {
+            (true, Some(last_id)) => after = Some(last_id),
+            _ => break,
+        }
+    }
+
+    Ok(files)
+}
+
+/// A page of the account files (`ListFilesResponse` has no pagination fields).
+#[derive(Deserialize)]
+struct FilesPage {
+    data: Vec<OpenAIFile>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+/// Lists all the account files, page by page.
+///
+/// NOTE: `oac.files().list()` only gets the first page, so the pages are fetched with raw requests.
+async fn list_org_files(oac: &OaClient) -> Result<Vec<OpenAIFile>> {
+    let mut files = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let page = with_retry(|| get_files_page(oac, after.as_deref())).await?;
+        let last_id = page.data.last().map(|file| file.id.clone());
+        files.extend(page.data);
+        match (page.has_more, last_id) {
+            (true, Some(last_id)) => after = Some(last_id),
+            _ => break,
+        }
+    }
+
+    Ok(files)
+}
+
+async fn get_files_page(oac: &OaClient, after: Option<&str>) -> std::result::Result<FilesPage, OpenAIError> {
+    let config = oac.config();
+    let mut query = vec![("limit", FILES_PAGE_LIMIT)];
+    if let Some(after) = after {
+        query.push(("after", after));
+    }
+    let page = oac.send_traced(
+        http_client()
+            .get(config.url("/files"))
+            .query(&config.query())
+            .query(&query)
+            .headers(config.headers()),
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+    Ok(page)
+}
+
+/// Re-attaches the account files named `file_names` which are not attached to the assistant
+/// anymore (e.g., association deleted in the dashboard), without reuploading.
+/// Returns the re-attached file names.
+///
+/// NOTE: When several account files have the same name, the most recent one is attached.
+pub async fn reattach_files(oac: &OaClient, asst_id: &AsstId, file_names: &[String]) -> Result<Vec<String>> {
+    check_writable("attach the files")?;
+    let attached = get_file_hashmap(oac, asst_id).await?;
+
+    let mut org_files = list_org_files(oac).await?;
+    org_files.sort_by_key(|f| std::cmp::Reverse(f.created_at));
+
+    let oa_assts = oac.assistants();
+    let oa_asst_files = oa_assts.files(asst_id);
+    let mut reattached: Vec<String> = Vec::new();
+    for org_file in org_files {
+        let name = &org_file.filename;
+        if !file_names.contains(name) || attached.contains_key(name) || reattached.contains(name) {
+            continue;
+        }
+        let req = CreateAssistantFileRequest {
+            file_id: org_file.id.clone(),
+        };
+        oac.traced("POST", format!("/assistants/{asst_id}/files"), oa_asst_files.create(req))
+            .await
+            .map_err(|err| format!("Can't re-attach '{name}'\n  cause: {err}"))?;
+        reattached.push(org_file.filename);
+    }
+
+    Ok(reattached)
+}
+
+/// Uploads the image to the account (`vision` purpose), to be attached to a message.
+pub async fn upload_image(oac: &OaClient, image: &Path) -> Result<FileId> {
+    check_writable("upload the image")?;
+    let ext = image.x_extension().to_lowercase();
+    if !IMAGE_EXTS.contains(&ext.as_str()) {
+        return Err(format!(
+            "'{}' is not a supported image ({})",
+            image.display(),
+            IMAGE_EXTS.join(", ")
+        )
+        .into());
+    }
+    if !image.is_file() {
+        return Err(format!("Image not found: {}", image.display()).into());
+    }
+
+    let req = CreateFileRequest {
+        file: image.into(),
+        purpose: "vision".into(),
+    };
+    let oa_file = oac.traced("POST", "/files", oac.files().create(req)).await?;
+
+    Ok(oa_file.id.into())
 }
 
 /// Uploads a file to an assistant (dirst to the account, then attaches to asst)
@@ -253,6 +1418,7 @@ pub async fn upload_file_by_name(
     file: &Path,
     force: bool,
 ) -> Result<(FileId, bool)> {
+    check_writable("upload the file")?;
     let file_name = file.x_file_name();
     let mut file_id_by_name = get_file_hashmap(oac, asst_id).await?;
 
@@ -265,13 +1431,110 @@ pub async fn upload_file_by_name(
         }
     }
 
-    // if we have old file_id, we delete the file.
+    // NOTE: The new file is uploaded and attached before the old one is deleted,
+    //       so a failure midway leaves the previous good state intact.
+
+    // Upload and attach the file
+    let term = Term::stdout();
+
+    // Print uploading (per-file lines are details)
+    let show_progress = show_details();
+    let upload_start = Instant::now();
+    if show_progress {
+        term.write_line(&format!(
+            "{} Uploading file '{}'",
+            ico_uploading(),
+            file.x_file_name()
+        ))?;
+    }
+
+    // Upload file
+    // NOTE: Retried, with a reclaim of an upload applied despite the error.
+    let oa_files = oac.files();
+    let started_at = unix_secs();
+    let oa_file = with_retry_reclaim(
+        || {
+            let req = CreateFileRequest {
+                file: file.into(),
+                purpose: "assistants".into(),
+            };
+            oac.traced("POST", "/files", oa_files.create(req))
+        },
+        || find_uploaded_file(oac, file_name, started_at),
+    )
+    .await
+    .map_err(|err| {
+        format!(
+            "Upload of '{}' failed, previous file kept.\n  cause: {}",
+            file.x_file_name(),
+            err
+        )
+    })?;
+
+    // Update print
+    if show_progress {
+        term.clear_last_lines(1)?;
+        let timing = if show_timings() {
+            format!(" ({:.1}s)", upload_start.elapsed().as_secs_f32())
+        } else {
+            String::new()
+        };
+        term.write_line(&format!(
+            "{} Uploaded file '{}'{timing}",
+            ico_uploaded(),
+            file.x_file_name()
+        ))?;
+    }
+
+    // Attach file to assistant
+    let oa_assts = oac.assistants();
+    let oa_assts_files = oa_assts.files(asst_id);
+    let asst_files_path = format!("/assistants/{asst_id}/files");
+    let asst_file_obj = match with_retry_reclaim(
+        || {
+            let req = CreateAssistantFileRequest {
+                file_id: oa_file.id.clone(),
+            };
+            oac.traced("POST", &asst_files_path, oa_assts_files.create(req))
+        },
+        || async {
+            let asst_file_path = format!("{asst_files_path}/{}", oa_file.id);
+            Ok(oac.traced("GET", asst_file_path, oa_assts_files.retrieve(&oa_file.id)).await.ok())
+        },
+    )
+    .await
+    {
+        Ok(asst_file_obj) => asst_file_obj,
+        Err(err) => {
+            // Rollback the upload so it does not become an orphan.
+            let rollback = match oac.traced("DELETE", format!("/files/{}", oa_file.id), oa_files.delete(&oa_file.id)).await {
+                Ok(_) => "new file deleted".to_string(),
+                Err(_) => format!("new file '{}' could not be deleted", oa_file.id),
+            };
+            return Err(format!(
+                "Attach of '{}' failed after upload ({rollback}), previous file kept.\n  cause: {}",
+                file.x_file_name(),
+                err
+            )
+            .into());
+        }
+    };
+
+    // Assert warning
+    if oa_file.id != asst_file_obj.id {
+        println!(
+            "SHOULD NOT HAPPEN, File id not matching {} {}",
+            redact(&oa_file.id),
+            redact(&asst_file_obj.id)
+        )
+    }
+
+    // if we have old file_id, we delete the file (new one is in place).
     if let Some(file_id) = file_id {
         // Delete the org file
-        let oa_files = oac.files();
-        if let Err(err) = oa_files.delete(&file_id).await {
+        if let Err(err) = oac.traced("DELETE", format!("/files/{file_id}"), oa_files.delete(&file_id)).await {
             println!(
-                "{} Can't delete file '{}'\n  cause: {}",
+                "{} New file attached, but can't delete old file '{}'\n  cause: {}",
                 ico_err(),
                 file.to_string_lossy(),
                 err
@@ -279,11 +1542,10 @@ pub async fn upload_file_by_name(
         }
 
         // Delete the asst_file association
-        let oa_assts = oac.assistants();
-        let oa_assts_files = oa_assts.files(asst_id);
-        if let Err(err) = oa_assts_files.delete(&file_id).await {
+        let asst_file_path = format!("{asst_files_path}/{file_id}");
+        if let Err(err) = oac.traced("DELETE", asst_file_path, oa_assts_files.delete(&file_id)).await {
             println!(
-                "{} Can't remove assistant file '{}'\n  cause: {}",
+                "{} New file attached, but can't remove old assistant file '{}'\n  cause: {}",
                 ico_err(),
                 file.x_file_name(),
                 err
@@ -291,50 +1553,320 @@ pub async fn upload_file_by_name(
         }
     }
 
-    // Upload and attach the file
-    let term = Term::stdout();
+    Ok((asst_file_obj.id.into(), true))
+}
+/// The file of this name uploaded since `since` (unix secs), if any.
+async fn find_uploaded_file(oac: &OaClient, file_name: &str, since: u32) -> Result<Option<OpenAIFile>> {
+    let files = list_org_files(oac).await?;
 
-    // Print uploading
-    term.write_line(&format!(
-        "{} Uploading file '{}'",
-        ico_uploading(),
-        file.x_file_name()
-    ))?;
+    Ok(files
+        .into_iter()
+        .find(|oa_file| oa_file.filename == file_name && oa_file.created_at >= since))
+}
 
-    // Upload file
-    let oa_files = oac.files();
-    let oa_file = oa_files
-        .create(CreateFileRequest {
-            file: file.into(),
-            purpose: "assistants".into(),
+fn unix_secs() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or(0)
+}
+
+/// Downloads the content of the file into `dst_dir/<file_id>.<ext>`, returns its path.
+pub async fn download_file(
+    oac: &OaClient,
+    file_id: &str,
+    dst_dir: &Path,
+    ext: &str,
+) -> Result<PathBuf> {
+    let config = oac.config();
+    let url = config.url(&format!("/files/{file_id}/content"));
+    let bytes = oac.send_traced(http_client().get(url).query(&config.query()).headers(config.headers()))
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    ensure_dir(dst_dir)?;
+    let file = dst_dir.join(format!("{file_id}.{ext}"));
+    std::fs::write(&file, bytes)?;
+
+    Ok(file)
+}
+// endregion: --- Files
+
+// region: --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ais::mock_server::{MockRequest, MockResponse, MockServer};
+    use serde_json::json;
+
+    fn query_param<'a>(req: &'a MockRequest, name: &str) -> Option<&'a str> {
+        req.query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    fn org_file(id: &str) -> serde_json::Value {
+        json!({
+            "id": id, "object": "file", "bytes": 10, "created_at": 1,
+            "filename": format!("{id}.rs"), "purpose": "assistants"
         })
+    }
+
+    fn asst_file(id: &str) -> serde_json::Value {
+        json!({ "id": id, "object": "assistant.file", "created_at": 1, "assistant_id": "asst_1" })
+    }
+
+    #[tokio::test]
+    async fn list_org_files_all_pages() -> Result<()> {
+        let server = MockServer::start(|req| {
+            let (data, has_more) = match query_param(req, "after") {
+                None => (vec![org_file("file_1"), org_file("file_2")], true),
+                Some("file_2") => (vec![org_file("file_3")], false),
+                Some(after) => panic!("Unexpected page after '{after}'"),
+            };
+            MockResponse::json(json!({ "object": "list", "data": data, "has_more": has_more }))
+        })
+        .await;
+
+        let files = list_org_files(&server.oa_client()).await?;
+
+        let ids: Vec<&str> = files.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["file_1", "file_2", "file_3"]);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|req| req.method == "GET" && req.path == "/v1/files" && query_param(req, "limit") == Some(FILES_PAGE_LIMIT)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_asst_files_all_pages() -> Result<()> {
+        let server = MockServer::start(|req| {
+            let (data, has_more) = match query_param(req, "after") {
+                None => (vec!["file_1", "file_2"], true),
+                Some("file_2") => (vec!["file_3", "file_4"], true),
+                Some("file_4") => (vec!["file_5"], false),
+                Some(after) => panic!("Unexpected page after '{after}'"),
+            };
+            MockResponse::json(json!({
+                "object": "list",
+                "data": data.iter().map(|id| asst_file(id)).collect::<Vec<_>>(),
+                "first_id": data.first(),
+                "last_id": data.last(),
+                "has_more": has_more
+            }))
+        })
+        .await;
+
+        let files = list_asst_files(&server.oa_client(), &"asst_1".to_string().into()).await?;
+
+        let ids: Vec<&str> = files.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["file_1", "file_2", "file_3", "file_4", "file_5"]);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests
+            .iter()
+            .all(|req| req.method == "GET" && req.path == "/v1/assistants/asst_1/files"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_thread_msg_stream_answers_function_calls() -> Result<()> {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/v1/threads/thread_1/messages") => msg_created(),
+            ("POST", "/v1/threads/thread_1/runs") => MockResponse::sse(&[
+                ("thread.run.created", json!({ "id": "run_1", "status": "queued" })),
+                ("thread.run.requires_action", json!({
+                    "id": "run_1",
+                    "status": "requires_action",
+                    "required_action": {
+                        "type": "submit_tool_outputs",
+                        "submit_tool_outputs": { "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": { "name": "get_time", "arguments": "{\"tz\":\"UTC\"}" }
+                        }]}
+                    }
+                })),
+                ("done", json!("[DONE]")),
+            ]),
+            ("POST", "/v1/threads/thread_1/runs/run_1/submit_tool_outputs") => MockResponse::sse(&[
+                ("thread.message.delta", json!({ "delta": { "content": [{ "text": { "value": "It is noon." } }] } })),
+                ("thread.run.completed", json!({
+                    "id": "run_1",
+                    "status": "completed",
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 }
+                })),
+                ("done", json!("[DONE]")),
+            ]),
+            (method, path) => panic!("Unexpected request {method} {path}"),
+        })
+        .await;
+
+        let mut options = RunOptions::default();
+        options.tool_handlers.insert(
+            "get_time",
+            Arc::new(|call: ToolCall| Ok(format!("12:00 for {}", call.arguments))),
+        );
+        let deltas = run_thread_msg_stream(
+            &server.oa_client(),
+            &"asst_1".to_string().into(),
+            &"thread_1".to_string().into(),
+            "What time is it?",
+            None,
+            &options,
+        )
         .await?;
+        let deltas: Vec<RunDelta> = deltas.collect::<Vec<_>>().await.into_iter().collect::<Result<_>>()?;
 
-    // Update print
-    term.clear_last_lines(1)?;
-    term.write_line(&format!(
-        "{} Uploaded file '{}'",
-        ico_uploaded(),
-        file.x_file_name()
-    ))?;
+        // -- The run goes on after the tool outputs, up to its completion.
+        assert!(matches!(&deltas[..], [RunDelta::Text(text), RunDelta::Completed { run_id, usage: Some(usage), truncated: false }]
+            if text == "It is noon." && run_id.as_str() == "run_1" && usage.total_tokens == 15));
 
-    // Attach file to assistant
-    let oa_assts = oac.assistants();
-    let oa_assts_files = oa_assts.files(asst_id);
-    let asst_file_obj = oa_assts_files
-        .create(CreateAssistantFileRequest {
-            file_id: oa_file.id.clone(),
+        // -- The function call is answered by its handler, and the run streamed on.
+        let submit = server
+            .requests()
+            .into_iter()
+            .find(|req| req.path.ends_with("/submit_tool_outputs"))
+            .expect("tool outputs submitted");
+        let body: serde_json::Value = serde_json::from_str(&submit.body)?;
+        assert_eq!(
+            body,
+            json!({
+                "tool_outputs": [{ "tool_call_id": "call_1", "output": "12:00 for {\"tz\":\"UTC\"}" }],
+                "stream": true
+            })
+        );
+        Ok(())
+    }
+
+    fn msg_created() -> MockResponse {
+        MockResponse::json(json!({
+            "id": "msg_1", "object": "thread.message", "created_at": 1, "thread_id": "thread_1",
+            "role": "user", "content": [], "file_ids": []
+        }))
+    }
+
+    fn token_limits() -> RunOptions {
+        RunOptions {
+            max_prompt_tokens: Some(1000),
+            max_completion_tokens: Some(5),
+            ..Default::default()
+        }
+    }
+
+    /// The create run request, which must be v2 for its token limits.
+    fn assert_v2_run_with_limits(server: &MockServer) -> Result<()> {
+        let create = server
+            .requests()
+            .into_iter()
+            .find(|req| req.method == "POST" && req.path == "/v1/threads/thread_1/runs")
+            .expect("run created");
+        assert_eq!(create.header("OpenAI-Beta"), Some("assistants=v2"));
+        let body: serde_json::Value = serde_json::from_str(&create.body)?;
+        assert_eq!(body["max_prompt_tokens"], 1000);
+        assert_eq!(body["max_completion_tokens"], 5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn run_thread_msg_incomplete_is_truncated() -> Result<()> {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/v1/threads/thread_1/messages") => msg_created(),
+            ("POST", "/v1/threads/thread_1/runs") => MockResponse::json(json!({ "id": "run_1" })),
+            ("GET", "/v1/threads/thread_1/runs/run_1") => {
+                assert_eq!(req.header("OpenAI-Beta"), Some("assistants=v2"));
+                MockResponse::json(json!({
+                    "id": "run_1",
+                    "status": "incomplete",
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 }
+                }))
+            }
+            ("GET", "/v1/threads/thread_1/messages") => MockResponse::json(json!({
+                "object": "list",
+                "data": [{
+                    "id": "msg_2", "object": "thread.message", "created_at": 2, "thread_id": "thread_1",
+                    "role": "assistant", "run_id": "run_1", "file_ids": [],
+                    "content": [{ "type": "text", "text": { "value": "It is", "annotations": [] } }]
+                }],
+                "first_id": "msg_2", "last_id": "msg_2", "has_more": false
+            })),
+            (method, path) => panic!("Unexpected request {method} {path}"),
         })
+        .await;
+
+        let images_dir = std::env::temp_dir();
+        let msg = run_thread_msg(
+            &server.oa_client(),
+            &"asst_1".to_string().into(),
+            &"thread_1".to_string().into(),
+            "What time is it?",
+            None,
+            &token_limits(),
+            &images_dir,
+        )
         .await?;
 
-    // Assert warning
-    if oa_file.id != asst_file_obj.id {
-        println!(
-            "SHOULD NOT HAPPEN, File id not matching {} {}",
-            oa_file.id, asst_file_obj.id
+        assert_eq!(msg.text, "It is [truncated]");
+        assert_eq!(msg.run_id.as_str(), "run_1");
+        assert_eq!(msg.usage.map(|usage| usage.total_tokens), Some(15));
+        assert_v2_run_with_limits(&server)
+    }
+
+    #[tokio::test]
+    async fn run_thread_msg_stream_incomplete_is_truncated() -> Result<()> {
+        let server = MockServer::start(|req| match (req.method.as_str(), req.path.as_str()) {
+            ("POST", "/v1/threads/thread_1/messages") => msg_created(),
+            ("POST", "/v1/threads/thread_1/runs") => MockResponse::sse(&[
+                ("thread.run.created", json!({ "id": "run_1", "status": "queued" })),
+                ("thread.message.delta", json!({ "delta": { "content": [{ "text": { "value": "It is" } }] } })),
+                ("thread.run.incomplete", json!({
+                    "id": "run_1",
+                    "status": "incomplete",
+                    "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 }
+                })),
+                ("done", json!("[DONE]")),
+            ]),
+            (method, path) => panic!("Unexpected request {method} {path}"),
+        })
+        .await;
+
+        let deltas = run_thread_msg_stream(
+            &server.oa_client(),
+            &"asst_1".to_string().into(),
+            &"thread_1".to_string().into(),
+            "What time is it?",
+            None,
+            &token_limits(),
         )
+        .await?;
+        let deltas: Vec<RunDelta> = deltas.collect::<Vec<_>>().await.into_iter().collect::<Result<_>>()?;
+
+        assert!(matches!(&deltas[..], [RunDelta::Text(_), RunDelta::Completed { run_id, usage: Some(usage), truncated: true }]
+            if run_id.as_str() == "run_1" && usage.total_tokens == 15));
+        assert_v2_run_with_limits(&server)
     }
 
-    Ok((asst_file_obj.id.into(), true))
+    #[tokio::test]
+    async fn delete_org_file_not_found_is_already_deleted() -> Result<()> {
+        let server = MockServer::start(|req| match req.path.as_str() {
+            "/v1/files/file_1" => MockResponse::json(json!({ "id": "file_1", "object": "file", "deleted": true })),
+            "/v1/files/file_2" => MockResponse::error(404, "File not found"),
+            _ => MockResponse::error(500, "The server had an error"),
+        })
+        .await;
+        let oac = server.oa_client();
+
+        assert!(delete_org_file(&oac, &"file_1".to_string().into()).await?);
+        assert!(!delete_org_file(&oac, &"file_2".to_string().into()).await?);
+        assert!(delete_org_file(&oac, &"file_3".to_string().into()).await.is_err());
+        assert!(server.requests().iter().all(|req| req.method == "DELETE"));
+        Ok(())
+    }
 }
-// endregion: --- Files
+
+// endregion: --- Tests
@@ -0,0 +1,173 @@
+//! A minimal http server of canned responses, to test the API calls without the network.
+
+use std::sync::{Arc, Mutex};
+
+use async_openai::config::OpenAIConfig;
+use async_openai::Client;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::ais::OaClient;
+
+/// A received request (query string without the `?`).
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    /// Lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct MockResponse {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl MockResponse {
+    pub fn json(value: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: value.to_string(),
+        }
+    }
+
+    /// The json error of the API, with the http `status`.
+    pub fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            ..Self::json(serde_json::json!({
+                "error": { "message": message, "type": "invalid_request_error", "param": null, "code": null }
+            }))
+        }
+    }
+
+    /// The server-sent events, by event name and data.
+    pub fn sse(events: &[(&str, serde_json::Value)]) -> Self {
+        let body = events
+            .iter()
+            .map(|(name, data)| format!("event: {name}\ndata: {data}\n\n"))
+            .collect();
+        Self {
+            status: 200,
+            content_type: "text/event-stream",
+            body,
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(&MockRequest) -> MockResponse + Send + Sync>;
+
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+impl MockServer {
+    /// Starts the server on a free local port, answering each request with the `handler`.
+    pub async fn start(handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests: Arc<Mutex<Vec<MockRequest>>> = Arc::default();
+        let handler: Handler = Arc::new(handler);
+
+        let server_requests = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let (handler, requests) = (handler.clone(), server_requests.clone());
+                tokio::spawn(answer(socket, handler, requests));
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// A client of the server, as configured by `OPENAI_API_BASE`.
+    pub fn oa_client(&self) -> OaClient {
+        OaClient {
+            client: Client::with_config(OpenAIConfig::new().with_api_base(&self.url).with_api_key("test")),
+            trace: false,
+        }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Reads the request and writes the handler response, closing the connection.
+async fn answer(
+    mut socket: TcpStream,
+    handler: Handler,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) -> Option<()> {
+    // -- Head, then the body of its content length.
+    let mut buf: Vec<u8> = Vec::new();
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let mut chunk = [0u8; 4096];
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let headers: Vec<(String, String)> = head
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let content_length: usize = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+    while buf.len() < head_end + content_length {
+        let mut chunk = [0u8; 4096];
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let mut request_line = head.lines().next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let req = MockRequest {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        headers,
+        body: String::from_utf8_lossy(&buf[head_end..]).to_string(),
+    };
+
+    let res = handler(&req);
+    requests.lock().unwrap().push(req);
+    let res = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        res.status,
+        res.content_type,
+        res.body.len(),
+        res.body
+    );
+    socket.write_all(res.as_bytes()).await.ok()?;
+    socket.shutdown().await.ok()
+}
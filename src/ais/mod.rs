@@ -3,14 +3,30 @@
 
 pub mod asst;
 pub mod msg;
-use crate::Result;
-use dotenv;
+pub mod profile;
+pub mod retry;
+#[cfg(test)]
+mod mock_server;
+use crate::utils::cli::text_dim;
+use crate::{Error, Result};
 
 
 // use crate::utils::files::get_glob_set;
 // use crate::Result;
-use async_openai::config::OpenAIConfig;
+use async_openai::config::{Config, OpenAIConfig};
+use async_openai::error::OpenAIError;
 use async_openai::Client;
+use derive_more::Deref;
+use futures::FutureExt;
+use profile::load_profile;
+use retry::{set_retry_options, RetryOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
 
 // endregion: --- Modules
 
@@ -20,16 +36,328 @@ use async_openai::Client;
 
 // const ENV_OPENAI_API_KEY: &str = "OPENAI_API_KEY";
 
-pub type OaClient = Client<OpenAIConfig>;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
 
-pub fn new_oa_client() -> Result<OaClient> {
-	if dotenv::var("OPENAI_API_KEY").is_ok(){
-		Ok(Client::new())
+/// The async-openai client, with the tracing of all its requests (see `ClientOptions::trace`).
+#[derive(Debug, Clone, Deref)]
+pub struct OaClient {
+	#[deref]
+	client: Client<OpenAIConfig>,
+	trace: bool,
+}
+
+/// The http client shared by the OaClient and the raw requests.
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+#[derive(Debug, Default)]
+pub struct ClientOptions {
+	pub request_timeout_secs: Option<u64>,
+	pub connect_timeout_secs: Option<u64>,
+	pub retry: RetryOptions,
+	/// Proxy url for all the requests, `HTTPS_PROXY` otherwise.
+	pub proxy: Option<String>,
+	/// Account from the profiles file, the `OPENAI_*` env variables otherwise (see `env_oa_config`).
+	pub profile: Option<String>,
+	/// File with the API key, instead of `OPENAI_API_KEY` (the other `OPENAI_*` env variables still apply).
+	pub api_key_file: Option<PathBuf>,
+	/// Prints a summary line of each API request (the `-vvv` verbosity).
+	pub trace: bool,
+}
+
+pub fn new_oa_client(options: ClientOptions) -> Result<OaClient> {
+	let oa_config = match (options.profile.as_deref(), options.api_key_file.as_deref()) {
+		(Some(profile), _) => Some(load_profile(profile)?.into()),
+		(None, Some(file)) => Some(env_oa_config().with_api_key(read_api_key_file(file)?)),
+		// NOTE: The `.env` files are loaded at init (see `Buddy::init`), not implicitly here.
+		(None, None) if std::env::var("OPENAI_API_KEY").is_ok() => Some(env_oa_config()),
+		(None, None) => None,
+	};
+
+	if let Some(oa_config) = oa_config {
+		let mut builder = reqwest::Client::builder()
+			.timeout(Duration::from_secs(
+				options.request_timeout_secs.unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS),
+			))
+			.connect_timeout(Duration::from_secs(
+				options.connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS),
+			));
+		if let Some(proxy) = get_proxy(options.proxy.as_deref())? {
+			builder = builder.proxy(proxy);
+		}
+		let http_client = builder.build()?;
+		let _ = HTTP_CLIENT.set(http_client.clone());
+		set_retry_options(options.retry);
+
+		Ok(OaClient {
+			client: Client::with_config(oa_config).with_http_client(http_client),
+			trace: options.trace,
+		})
 	} else {
-		println!("No ENV_OPENAI_API_KEY env variable. Please set it.");
+		Err(Error::MissingApiKey)
+	}
+}
+
+/// The API key in the file (trimmed).
+///
+/// NOTE: The errors tell the file only, never its content.
+fn read_api_key_file(file: &Path) -> Result<String> {
+	let content = std::fs::read_to_string(file)
+		.map_err(|err| format!("Can't read the API key file '{}': {err}", file.display()))?;
+	let key = content.trim();
+	if key.is_empty() {
+		return Err(format!("The API key file '{}' is empty", file.display()).into());
+	}
+
+	Ok(key.to_string())
+}
+
+/// Returns the proxy from the `proxy` url, or from the `HTTPS_PROXY` env variable,
+/// with the `NO_PROXY` exclusions.
+///
+/// NOTE: reqwest would silently ignore a malformed env proxy, so it is parsed here.
+fn get_proxy(proxy_url: Option<&str>) -> Result<Option<reqwest::Proxy>> {
+	let (url, source) = match proxy_url {
+		Some(url) => (url.to_string(), "proxy config"),
+		None => {
+			let env_url = ["HTTPS_PROXY", "https_proxy"]
+				.into_iter()
+				.find_map(|var| std::env::var(var).ok());
+			match env_url {
+				Some(url) => (url, "HTTPS_PROXY"),
+				None => return Ok(None),
+			}
+		}
+	};
+
+	let proxy = reqwest::Proxy::all(&url)
+		.map_err(|err| format!("Malformed proxy url '{url}' (from {source}): {err}"))?
+		.no_proxy(reqwest::NoProxy::from_env());
+
+	Ok(Some(proxy))
+}
+
+/// Returns the http client for the requests not covered by async-openai.
+pub fn http_client() -> reqwest::Client {
+	HTTP_CLIENT.get().cloned().unwrap_or_default()
+}
+
+/// The config from the `OPENAI_API_KEY` env variable, with the `OPENAI_API_BASE`
+/// (e.g., a proxy or gateway) and `OPENAI_ORG_ID` ones when set.
+///
+/// NOTE: Azure OpenAI deployments need an OpenAI compatible endpoint here,
+///       the client is not built with the Azure config (api version, deployment).
+fn env_oa_config() -> OpenAIConfig {
+	oa_config_from_vars(|name| std::env::var(name).ok())
+}
+
+/// The config from the `OPENAI_*` variables of the `var` lookup (blank ones ignored), without the API key.
+fn oa_config_from_vars(var: impl Fn(&str) -> Option<String>) -> OpenAIConfig {
+	let env_var = |name: &str| var(name).filter(|value| !value.trim().is_empty());
+
+	let mut config = OpenAIConfig::default();
+	if let Some(api_base) = env_var("OPENAI_API_BASE") {
+		config = config.with_api_base(api_base);
+	}
+	if let Some(org_id) = env_var("OPENAI_ORG_ID") {
+		config = config.with_org_id(org_id);
+	}
+	config
+}
+
+impl OaClient {
+	/// Sends the raw request, with a summary line (method, path, status, time) when traced.
+	pub async fn send_traced(&self, req: reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+		let (client, req) = req.build_split();
+		let req = req?;
+		if !self.trace {
+			return client.execute(req).await;
+		}
+
+		let (method, path) = (req.method().clone(), req.url().path().to_string());
+		let start = Instant::now();
+		let res = client.execute(req).await;
+		let outcome = match &res {
+			Ok(res) => res.status().to_string(),
+			Err(err) => err.to_string(),
+		};
+		print_trace(method, path, outcome, start);
+
+		res
+	}
+
+	/// Awaits the async-openai `call`, with a summary line (method, path, outcome, time) when traced.
+	///
+	/// NOTE: async-openai does not expose the response status, so the outcome is `ok` or the error.
+	pub async fn traced<T>(
+		&self,
+		method: &str,
+		path: impl Display,
+		call: impl Future<Output = std::result::Result<T, OpenAIError>>,
+	) -> std::result::Result<T, OpenAIError> {
+		if !self.trace {
+			return call.await;
+		}
+
+		let start = Instant::now();
+		let res = call.await;
+		let outcome = match &res {
+			Ok(_) => "ok".to_string(),
+			Err(err) => err.to_string(),
+		};
+		print_trace(method, path, outcome, start);
+
+		res
+	}
+}
+
+fn print_trace(method: impl Display, path: impl Display, outcome: String, start: Instant) {
+	println!(
+		"{}",
+		text_dim(format!("  {method} {path} -> {outcome} ({}ms)", start.elapsed().as_millis()))
+	);
+}
+
+/// Checks that the API host can be reached (DNS, connection, timeout).
+///
+/// Any http response is fine here, auth and api errors are reported by the first real call.
+pub async fn check_connection(oac: &OaClient) -> Result<()> {
+	let config = oac.config();
+	let res = oac.send_traced(http_client().get(config.url("/models")).headers(config.headers())).await;
+
+	match res {
+		Ok(_) => Ok(()),
+		Err(err) if err.is_connect() || err.is_timeout() => Err(Error::Connect(format!(
+			"Can't connect to the OpenAI API at '{}'\n  cause: {err}",
+			config.api_base()
+		))),
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// Checks that the model exists and is available to the API key.
+pub async fn check_model(oac: &OaClient, model: &str) -> Result<()> {
+	oac.traced("GET", format!("/models/{model}"), oac.models().retrieve(model))
+		.await
+		.map_err(explain_error)?;
 
-		Err("No openai api key in env".into())
+	Ok(())
+}
+
+/// Tells apart the auth and network errors from the other API errors.
+pub fn explain_error(err: OpenAIError) -> Error {
+	match &err {
+		OpenAIError::ApiError(api_err)
+			if api_err.code.as_ref().and_then(|c| c.as_str()) == Some("invalid_api_key") =>
+		{
+			format!("Authentication failed, check OPENAI_API_KEY ({})", api_err.message).into()
+		}
+		OpenAIError::Reqwest(req_err) if req_err.is_connect() || req_err.is_timeout() => {
+			Error::Connect(format!("Network error, can't reach the OpenAI API ({req_err})"))
+		}
+		_ => err.into(),
 	}
 }
 
 // endregion: --- Client
+
+// region: --- Read Only
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// In read-only mode (e.g., `--inspect`), all the creates, modifies, deletes,
+/// uploads, and runs error instead of calling the API.
+pub fn set_read_only(read_only: bool) {
+	READ_ONLY.store(read_only, Ordering::Relaxed);
+}
+
+pub fn is_read_only() -> bool {
+	READ_ONLY.load(Ordering::Relaxed)
+}
+
+/// To be called first by every mutating call, errors in read-only mode.
+pub fn check_writable(action: &str) -> Result<()> {
+	if is_read_only() {
+		return Err(format!("Read-only mode, can't {action}").into());
+	}
+	Ok(())
+}
+
+// endregion: --- Read Only
+
+// region: --- Interrupt
+
+static RUN_ACTIVE: AtomicBool = AtomicBool::new(false);
+static INTERRUPT: Notify = Notify::const_new();
+
+/// To be called on Ctrl-C, interrupts the active run (see `ActiveRun`).
+/// Returns false when no run is active (e.g., uploading).
+pub fn interrupt_run() -> bool {
+	let active = RUN_ACTIVE.load(Ordering::Relaxed);
+	if active {
+		INTERRUPT.notify_one();
+	}
+	active
+}
+
+/// Marks a run as active, and so interruptible by `interrupt_run`, until dropped.
+pub struct ActiveRun;
+
+impl ActiveRun {
+	pub fn start() -> Self {
+		// A Ctrl-C racing the end of a previous run must not interrupt this one.
+		let _ = INTERRUPT.notified().now_or_never();
+		RUN_ACTIVE.store(true, Ordering::Relaxed);
+		Self
+	}
+
+	/// Resolves once the run is interrupted (also when by then).
+	pub async fn interrupted(&self) {
+		INTERRUPT.notified().await
+	}
+}
+
+impl Drop for ActiveRun {
+	fn drop(&mut self) {
+		RUN_ACTIVE.store(false, Ordering::Relaxed);
+	}
+}
+
+// endregion: --- Interrupt
+
+// region: --- Tests
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A client of the config from the `vars` (instead of the process env, shared by the parallel tests).
+	fn client_from_vars(vars: &[(&str, &str)]) -> Client<OpenAIConfig> {
+		let config = oa_config_from_vars(|name| {
+			vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+		});
+		Client::with_config(config)
+	}
+
+	#[test]
+	fn oa_config_api_base() {
+		let default_base = OpenAIConfig::default().api_base().to_string();
+
+		let client = client_from_vars(&[("OPENAI_API_BASE", "http://localhost:8080/v1"), ("OPENAI_ORG_ID", "org_1")]);
+		assert_eq!(client.config().api_base(), "http://localhost:8080/v1");
+		assert_eq!(client.config().url("/assistants"), "http://localhost:8080/v1/assistants");
+		assert_eq!(
+			client.config().headers().get("OpenAI-Organization").and_then(|v| v.to_str().ok()),
+			Some("org_1")
+		);
+
+		// -- A blank value keeps the default base.
+		let client = client_from_vars(&[("OPENAI_API_BASE", "  ")]);
+		assert_eq!(client.config().api_base(), default_base);
+
+		let client = client_from_vars(&[]);
+		assert_eq!(client.config().api_base(), default_base);
+	}
+}
+
+// endregion: --- Tests
@@ -1,6 +1,75 @@
-use async_openai::types::{CreateMessageRequest, MessageObject, MessageContent};
+use std::path::PathBuf;
 
-use crate::Result;
+use async_openai::types::{
+    CreateMessageRequest, MessageContent, MessageContentTextAnnotations, MessageObject, MessageRole,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ais::asst::{RunId, Usage};
+
+
+// region --- Types
+
+/// The assistant answer of a run.
+#[derive(Debug)]
+pub struct AssistantMessage {
+    pub text: String,
+    /// Local files of the message images (downloaded after the run).
+    pub images: Vec<PathBuf>,
+    pub run_id: RunId,
+    pub usage: Option<Usage>,
+    /// Retrieval use of the run, only fetched for the verbose output.
+    pub retrieval: Option<RetrievalInfo>,
+    /// The retrieved files cited in the text.
+    pub citations: Vec<Citation>,
+}
+
+/// A file citation of an answer (a text annotation).
+#[derive(Debug)]
+pub struct Citation {
+    pub file_id: String,
+    /// The quoted span of the file.
+    pub quote: String,
+}
+
+/// How much the retrieval tool was used for an answer.
+///
+/// NOTE: The API does not tell the chunks retrieved, so the citations are the closest hint.
+#[derive(Debug)]
+pub struct RetrievalInfo {
+    /// Retrieval tool calls of the run.
+    pub calls: usize,
+    /// File citations in the answer.
+    pub citations: usize,
+}
+
+/// A user message with an image, as content parts (text first, then the image).
+#[derive(Debug, Serialize)]
+pub struct ImageMessage {
+    role: &'static str,
+    content: Vec<ContentPart>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart {
+    Text { text: String },
+    ImageFile { image_file: ImageFileRef },
+}
+
+#[derive(Debug, Serialize)]
+struct ImageFileRef {
+    file_id: String,
+}
+
+/// A message in the chat completions format.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChatMsg {
+    pub role: String,
+    pub content: String,
+}
+
+// endregion --- Types
 
 
 // region --- Message Constructors
@@ -13,29 +82,131 @@ pub fn user_msg(content: impl Into<String>) -> CreateMessageRequest {
     }
 }
 
+/// The user message with the uploaded image `file_id` (see `asst::upload_image`).
+pub fn user_msg_with_image(content: impl Into<String>, file_id: impl Into<String>) -> ImageMessage {
+    ImageMessage {
+        role: "user",
+        content: vec![
+            ContentPart::Text { text: content.into() },
+            ContentPart::ImageFile {
+                image_file: ImageFileRef { file_id: file_id.into() },
+            },
+        ],
+    }
+}
+
 // endregion --- Message Constructors
 
 
 // region --- Content Constructor
 
-pub fn get_text_content(msg: MessageObject) -> Result<String> {
-    // -- Get the first content item
-    let msg_content = msg
-    .content
-    .into_iter()
-    .next()
-    .ok_or_else(|| "No message content found".to_string())?;
-
-    // -- Get the text
-    let txt = match  msg_content {
-        MessageContent::Text(text) => text.text.value,
-        MessageContent::ImageFile(_) => {
-            return Err("Message image not supported yet".into());
+/// Rough token count of the text (~4 chars per token), enough for the thresholds.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Returns the file citations in the text parts of the message.
+pub fn get_file_citations(msg: &MessageObject) -> Vec<Citation> {
+    msg.content
+        .iter()
+        .flat_map(|content| match content {
+            MessageContent::Text(text) => text.text.annotations.as_slice(),
+            MessageContent::ImageFile(_) => &[],
+        })
+        .filter_map(|annotation| match annotation {
+            MessageContentTextAnnotations::FileCitation(citation) => Some(Citation {
+                file_id: citation.file_citation.file_id.clone(),
+                quote: citation.file_citation.quote.clone(),
+            }),
+            MessageContentTextAnnotations::FilePath(_) => None,
+        })
+        .collect()
+}
+
+/// Returns the concatenated text parts and the image file ids of the message.
+pub fn get_text_and_image_ids(msg: MessageObject) -> (String, Vec<String>) {
+    let mut texts = Vec::new();
+    let mut image_ids = Vec::new();
+    for content in msg.content {
+        match content {
+            MessageContent::Text(text) => texts.push(text.text.value),
+            MessageContent::ImageFile(image) => image_ids.push(image.image_file.file_id),
         }
+    }
+
+    (texts.join("\n\n"), image_ids)
+}
+
+/// Returns the text of the message, the parts in order, with the images noted as `[image: <file_id>]`.
+pub fn get_message_text(mut msg: MessageObject) -> String {
+    // Single text part (most messages), as is.
+    if let [MessageContent::Text(_)] = msg.content.as_slice() {
+        if let Some(MessageContent::Text(text)) = msg.content.pop() {
+            return text.text.value;
+        }
+    }
+
+    let parts: Vec<String> = msg
+        .content
+        .into_iter()
+        .map(|content| match content {
+            MessageContent::Text(text) => text.text.value,
+            MessageContent::ImageFile(image) => format!("[image: {}]", image.image_file.file_id),
+        })
+        .collect();
+
+    parts.join("\n\n")
+}
+
+/// Returns the message in the chat completions format (`role`, `content`),
+/// the content as in `get_message_text`.
+pub fn to_chat_msg(msg: MessageObject) -> ChatMsg {
+    let role = match msg.role {
+        MessageRole::User => "user",
+        MessageRole::Assistant => "assistant",
     };
 
-    Ok(txt)
+    ChatMsg {
+        role: role.to_string(),
+        content: get_message_text(msg),
+    }
 }
 
+// endregion --- Content Constructor
+
+
+// region --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn msg_of(content: serde_json::Value) -> MessageObject {
+        serde_json::from_value(json!({
+            "id": "msg_1", "object": "thread.message", "created_at": 1, "thread_id": "thread_1",
+            "role": "assistant", "content": content, "assistant_id": null, "run_id": null,
+            "file_ids": [], "metadata": null
+        }))
+        .unwrap()
+    }
+
+    fn text_part(value: &str) -> serde_json::Value {
+        json!({ "type": "text", "text": { "value": value, "annotations": [] } })
+    }
+
+    fn image_part(file_id: &str) -> serde_json::Value {
+        json!({ "type": "image_file", "image_file": { "file_id": file_id } })
+    }
+
+    #[test]
+    fn get_message_text_parts_in_order() {
+        let msg = msg_of(json!([text_part("Before"), image_part("file_1"), text_part("After")]));
+        assert_eq!(get_message_text(msg), "Before\n\n[image: file_1]\n\nAfter");
+
+        let msg = msg_of(json!([text_part("Only text")]));
+        assert_eq!(get_message_text(msg), "Only text");
+    }
+}
 
-// endregion --- Content Constructor
\ No newline at end of file
+// endregion --- Tests
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use async_openai::config::OpenAIConfig;
+use serde::Deserialize;
+
+use crate::utils::files::load_from_toml;
+use crate::Result;
+
+// region: --- Profiles
+
+/// An OpenAI account, from the profiles file (see `profiles_file`).
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+	pub api_key: String,
+	pub api_base: Option<String>,
+	pub org_id: Option<String>,
+}
+
+impl From<Profile> for OpenAIConfig {
+	fn from(profile: Profile) -> Self {
+		let mut config = OpenAIConfig::new().with_api_key(profile.api_key);
+		if let Some(api_base) = profile.api_base {
+			config = config.with_api_base(api_base);
+		}
+		if let Some(org_id) = profile.org_id {
+			config = config.with_org_id(org_id);
+		}
+		config
+	}
+}
+
+/// The `rust-buddy/profiles.toml` of the user config dir
+/// (`$XDG_CONFIG_HOME`, or `~/.config`), one `[<profile>]` table per account.
+pub fn profiles_file() -> Result<PathBuf> {
+	let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+		Some(dir) => PathBuf::from(dir),
+		None => {
+			let home = std::env::var_os("HOME").ok_or("No HOME env variable to find the profiles")?;
+			PathBuf::from(home).join(".config")
+		}
+	};
+
+	Ok(config_dir.join("rust-buddy").join("profiles.toml"))
+}
+
+pub fn load_profile(name: &str) -> Result<Profile> {
+	let file = profiles_file()?;
+	let mut profiles: HashMap<String, Profile> = load_from_toml(&file)
+		.map_err(|err| format!("Can't load the profiles '{}': {err}", file.display()))?;
+
+	profiles
+		.remove(name)
+		.ok_or_else(|| format!("No profile '{name}' in '{}'", file.display()).into())
+}
+
+// endregion: --- Profiles
@@ -0,0 +1,262 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use async_openai::error::OpenAIError;
+use tokio::time::sleep;
+
+use crate::{Error, Result};
+
+// region: --- Constants
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_BREAKER_THRESHOLD: u32 = 5;
+const DEFAULT_BREAKER_COOLDOWN_SECS: u64 = 30;
+const BASE_DELAY_MS: u64 = 500;
+const MAX_DELAY_MS: u64 = 8_000;
+
+// endregion: --- Constants
+
+// region: --- Types
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryOptions {
+	/// Retries after the first attempt.
+	pub max_retries: u32,
+	/// Consecutive failures opening the circuit breaker.
+	pub breaker_threshold: u32,
+	/// How long the open breaker fails fast before letting calls through again.
+	pub breaker_cooldown: Duration,
+}
+
+impl Default for RetryOptions {
+	fn default() -> Self {
+		Self {
+			max_retries: DEFAULT_MAX_RETRIES,
+			breaker_threshold: DEFAULT_BREAKER_THRESHOLD,
+			breaker_cooldown: Duration::from_secs(DEFAULT_BREAKER_COOLDOWN_SECS),
+		}
+	}
+}
+
+#[derive(Default)]
+struct Breaker {
+	consecutive_failures: u32,
+	open_until: Option<Instant>,
+}
+
+static RETRY_OPTIONS: OnceLock<RetryOptions> = OnceLock::new();
+static BREAKER: Mutex<Breaker> = Mutex::new(Breaker {
+	consecutive_failures: 0,
+	open_until: None,
+});
+
+// endregion: --- Types
+
+// region: --- Retry
+
+/// Sets the retry options for the session (first call wins).
+pub fn set_retry_options(options: RetryOptions) {
+	let _ = RETRY_OPTIONS.set(options);
+}
+
+fn retry_options() -> RetryOptions {
+	RETRY_OPTIONS.get().copied().unwrap_or_default()
+}
+
+/// Runs `f` and retries it on transient errors (rate limits included),
+/// with an exponential backoff and full jitter.
+///
+/// All the calls share one circuit breaker. Once `breaker_threshold` calls failed in a row,
+/// calls fail fast for `breaker_cooldown` instead of retrying (rate limits do not count,
+/// the API is up).
+///
+/// NOTE: Only for idempotent requests, a timed out create might have been applied.
+pub async fn with_retry<T, F, Fut>(mut f: F) -> Result<T>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = std::result::Result<T, OpenAIError>>,
+{
+	let options = retry_options();
+	let mut attempt = 0;
+
+	loop {
+		check_breaker()?;
+
+		match f().await {
+			Ok(res) => {
+				record_success();
+				return Ok(res);
+			}
+			Err(err) if is_transient(&err) => {
+				record_retry(&err, &options);
+				if attempt >= options.max_retries {
+					return Err(err.into());
+				}
+				sleep(backoff_delay(attempt)).await;
+				attempt += 1;
+			}
+			// The API answered, so it is up.
+			Err(err) => {
+				record_success();
+				return Err(err.into());
+			}
+		}
+	}
+}
+
+/// Like `with_retry`, for the non idempotent creates. Before each retry, `reclaim` looks up
+/// whether the failed attempt was applied anyway (e.g., by a `new_nonce` metadata tag),
+/// in which case its object is returned instead of creating a second one.
+pub async fn with_retry_reclaim<T, F, Fut, R, RFut>(mut f: F, mut reclaim: R) -> Result<T>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = std::result::Result<T, OpenAIError>>,
+	R: FnMut() -> RFut,
+	RFut: Future<Output = Result<Option<T>>>,
+{
+	let options = retry_options();
+	let mut attempt = 0;
+
+	loop {
+		check_breaker()?;
+
+		match f().await {
+			Ok(res) => {
+				record_success();
+				return Ok(res);
+			}
+			Err(err) if is_transient(&err) => {
+				record_retry(&err, &options);
+				if attempt >= options.max_retries {
+					return Err(err.into());
+				}
+				sleep(backoff_delay(attempt)).await;
+				attempt += 1;
+				// The response might have been lost after the create was applied.
+				if let Some(res) = reclaim().await? {
+					record_success();
+					return Ok(res);
+				}
+			}
+			Err(err) => {
+				record_success();
+				return Err(err.into());
+			}
+		}
+	}
+}
+
+/// Fails fast while the circuit breaker is open.
+pub fn check_breaker() -> Result<()> {
+	let mut breaker = BREAKER.lock().map_err(|_| "Circuit breaker lock poisoned")?;
+	match breaker.open_until {
+		Some(until) if Instant::now() < until => Err(format!(
+			"OpenAI appears to be down ({} failures in a row), retry in {}s",
+			breaker.consecutive_failures,
+			(until - Instant::now()).as_secs() + 1
+		)
+		.into()),
+		// Cooldown over, let the next call probe the API.
+		Some(_) => {
+			breaker.open_until = None;
+			Ok(())
+		}
+		None => Ok(()),
+	}
+}
+
+fn record_success() {
+	if let Ok(mut breaker) = BREAKER.lock() {
+		breaker.consecutive_failures = 0;
+		breaker.open_until = None;
+	}
+}
+
+/// A rate limited call still proves the API up, the others count toward the breaker.
+fn record_retry(err: &OpenAIError, options: &RetryOptions) {
+	if is_rate_limited(err) {
+		record_success();
+	} else {
+		record_failure(options);
+	}
+}
+
+fn record_failure(options: &RetryOptions) {
+	if let Ok(mut breaker) = BREAKER.lock() {
+		breaker.consecutive_failures += 1;
+		if breaker.consecutive_failures >= options.breaker_threshold {
+			breaker.open_until = Some(Instant::now() + options.breaker_cooldown);
+		}
+	}
+}
+
+/// Returns true when the error is worth retrying (network or server side), false for
+/// the others (e.g., auth, invalid request) or the errors already turned into messages.
+pub fn is_transient_error(err: &Error) -> bool {
+	match err {
+		Error::OpenAI(err) => is_transient(err),
+		Error::Reqwest(err) => {
+			err.is_connect()
+				|| err.is_timeout()
+				|| err
+					.status()
+					.is_some_and(|s| s.is_server_error() || s == reqwest::StatusCode::TOO_MANY_REQUESTS)
+		}
+		Error::Connect(_) => true,
+		_ => false,
+	}
+}
+
+/// Connection problems, timeouts, rate limits, server errors, and non json (e.g., gateway) responses.
+fn is_transient(err: &OpenAIError) -> bool {
+	if is_rate_limited(err) {
+		return true;
+	}
+	match err {
+		OpenAIError::Reqwest(err) => {
+			err.is_connect()
+				|| err.is_timeout()
+				|| err.status().is_some_and(|s| s.is_server_error())
+		}
+		OpenAIError::ApiError(api_err) => api_err.r#type.as_deref() == Some("server_error"),
+		OpenAIError::JSONDeserialize(_) => true,
+		_ => false,
+	}
+}
+
+/// Too many requests, but not an exhausted quota (which a retry does not fix).
+fn is_rate_limited(err: &OpenAIError) -> bool {
+	match err {
+		OpenAIError::Reqwest(err) => err.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS),
+		OpenAIError::ApiError(api_err) => {
+			api_err.code.as_ref().and_then(|code| code.as_str()) == Some("rate_limit_exceeded")
+		}
+		_ => false,
+	}
+}
+
+/// Full jitter: random delay between 0 and the exponential backoff cap.
+pub fn backoff_delay(attempt: u32) -> Duration {
+	let cap = BASE_DELAY_MS.saturating_mul(1 << attempt.min(16)).min(MAX_DELAY_MS);
+
+	Duration::from_millis(random_u64() % (cap + 1))
+}
+
+/// Client generated id, to tag a created object and find it back (see `with_retry_reclaim`).
+pub fn new_nonce() -> String {
+	format!("{:016x}{:016x}", random_u64(), random_u64())
+}
+
+fn random_u64() -> u64 {
+	let mut hasher = RandomState::new().build_hasher();
+	if let Ok(now) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+		hasher.write_u128(now.as_nanos());
+	}
+
+	hasher.finish()
+}
+
+// endregion: --- Retry
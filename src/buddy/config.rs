@@ -1,33 +1,379 @@
-use serde::Deserialize;
+use globset::Glob;
+use serde::{Deserialize, Serialize};
 
-use crate::ais::asst;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::ais::{asst, retry::RetryOptions, ClientOptions};
+use crate::utils::cli::{parse_color, Theme};
+use crate::utils::files::BundleOptions;
+use crate::utils::transform::{ExternalCommand, FileTransform, Redact};
+use crate::{Error, Result};
 
+/// The models usable by the assistants (see `allow_unknown_model` for the others).
+const KNOWN_MODELS: &[&str] = &[
+    "gpt-4o",
+    "gpt-4o-2024-05-13",
+    "gpt-4o-mini",
+    "gpt-4o-mini-2024-07-18",
+    "gpt-4-turbo",
+    "gpt-4-turbo-2024-04-09",
+    "gpt-4-turbo-preview",
+    "gpt-4-0125-preview",
+    "gpt-4-1106-preview",
+    "gpt-4",
+    "gpt-4-0613",
+    "gpt-3.5-turbo",
+    "gpt-3.5-turbo-0125",
+    "gpt-3.5-turbo-1106",
+    "gpt-3.5-turbo-16k",
+];
 
-#[derive(Debug, Deserialize)]
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 
 pub(super) struct  Config {
     pub name: String,
     pub model: String,
-    pub instructions_file: String,
+    pub instructions_file: InstructionsFiles,
     pub file_bundles: Vec<FileBundle>,
+    /// Accept the models not in the known list, e.g., a new or fine-tuned one (default false).
+    pub allow_unknown_model: Option<bool>,
+    /// Data dir (bundles, conversations, events), `.buddy` by default.
+    pub data_dir: Option<String>,
+    /// Timeout of each OpenAI request (default 30s).
+    pub request_timeout_secs: Option<u64>,
+    /// Timeout of the connection to the OpenAI API (default 10s).
+    pub connect_timeout_secs: Option<u64>,
+    /// Text added before the instructions of every assistant on upload (none by default).
+    pub instruction_prefix: Option<String>,
+    /// Text added after the instructions of every assistant on upload (none by default).
+    pub instruction_suffix: Option<String>,
+    /// Template of the uploaded bundle file names, with the `{name}`, `{bundle}`, `{asst_id}`,
+    /// and `{ext}` placeholders (default `{name}-{bundle}-bundle-{asst_id}.{ext}`).
+    pub bundle_file_name: Option<String>,
+    /// OpenAI account from the user profiles file (`OPENAI_API_KEY` by default).
+    pub profile: Option<String>,
+    /// File with the OpenAI API key, relative to the buddy dir (`OPENAI_API_KEY` by default).
+    pub api_key_file: Option<String>,
+    /// Proxy url for the OpenAI requests (`HTTPS_PROXY` by default).
+    pub proxy: Option<String>,
+    /// Retries of a failed OpenAI read request (default 3).
+    pub max_retries: Option<u32>,
+    /// Failures in a row before failing fast as "OpenAI appears to be down" (default 5).
+    pub breaker_threshold: Option<u32>,
+    /// How long to fail fast once the breaker is open (default 30s).
+    pub breaker_cooldown_secs: Option<u64>,
+    /// Max prompt tokens of each run (no limit by default).
+    pub max_prompt_tokens: Option<u32>,
+    /// Max completion tokens of each run, longer answers are truncated (no limit by default).
+    pub max_completion_tokens: Option<u32>,
+    /// Refetches of an answer not listed yet right after its run completed (default 2).
+    pub answer_fetch_retries: Option<u32>,
+    /// What to do before a run when the conversation gets too long (`off` by default).
+    pub context_guardrail: Option<ContextGuardrail>,
+    /// Estimated tokens of the conversation triggering the guardrail (default 100000).
+    pub context_max_tokens: Option<u32>,
+    /// Bundle files uploaded at the same time (default 4).
+    pub upload_concurrency: Option<u32>,
+    /// Print the last exchanges of the conversation at launch, like `--resume` (default false).
+    pub resume: Option<bool>,
+    /// Prices of the models in USD per 1M tokens (`[prices.<model>]`), for the `/tokens` cost, none by default.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub prices: HashMap<String, ModelPrice>,
+    /// Functions of the assistant answered by an external command (`[[tools]]`), none by default.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolCommand>,
+    /// Instructions settings (`[instructions]`), none by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<InstructionsConfig>,
+    /// Cli colors (`[theme]`), the default palette when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeConfig>,
+    /// Additional assistants (`[[assistant]]`), switched with `/buddy <name>`.
+    #[serde(default, rename = "assistant", skip_serializing_if = "Vec::is_empty")]
+    pub assistants: Vec<AsstConfig>,
 }
 
+/// Price of a model, in USD per 1M tokens.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(super) struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+/// A function answered by an external command: the call arguments (json) on its stdin,
+/// the function name in the `BUDDY_TOOL` env variable, and its stdout as the output.
+///
+/// NOTE: The function itself is declared on the assistant (e.g., in the OpenAI dashboard),
+///       the created assistants only have the retrieval tool.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(super) struct ToolCommand {
+    pub name: String,
+    pub command: Vec<String>,
+}
 
-#[derive(Debug, Deserialize)]
+/// An assistant of the buddy. The top level config fields are the main one.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+
+pub(super) struct AsstConfig {
+    pub name: String,
+    pub model: String,
+    pub instructions_file: InstructionsFiles,
+    #[serde(default)]
+    pub file_bundles: Vec<FileBundle>,
+}
+
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 
 pub(super) struct FileBundle {
     pub bundle_name: String,
     pub src_dir: String,
     pub dst_ext: String,
     pub src_globs: Vec<String>,
+    /// Globs of the source files to leave out, even when matching `src_globs` (none by default).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_globs: Vec<String>,
+    /// Strip trailing whitespace and normalize line endings to LF (default false).
+    #[serde(default)]
+    pub normalize: bool,
+    /// Split the source files bigger than this size (in bytes) into parts (no split by default).
+    pub chunk_max_bytes: Option<u64>,
+    /// Transforms of each file content before bundling, in order (none by default).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transforms: Vec<TransformConfig>,
+    /// Max size of the bundle file, in bytes (no limit by default).
+    pub max_bundle_bytes: Option<u64>,
+    /// Max number of source files in the bundle (no limit by default).
+    pub max_files: Option<usize>,
+    /// Behavior when the bundle exceeds `max_bundle_bytes` or `max_files` (`error` by default).
+    #[serde(default)]
+    pub on_limit: BundleLimitAction,
+}
+
+/// Behavior when a bundle exceeds its limits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum BundleLimitAction {
+    /// The bundle fails, and is not uploaded.
+    #[default]
+    Error,
+    /// The files past the limit are left out of the bundle, with a warning.
+    Truncate,
+}
+
+/// A file transform (`[[file_bundles.transforms]]`), by `type`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum TransformConfig {
+    /// Replaces the regex `patterns` matches (`[REDACTED]` by default).
+    Redact {
+        patterns: Vec<String>,
+        replacement: Option<String>,
+    },
+    /// Pipes the content through the command (e.g., `["sed", "/^\\s*\\/\\//d"]`).
+    Command { command: Vec<String> },
+}
+
+
+/// Behavior when the conversation exceeds `context_max_tokens`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ContextGuardrail {
+    #[default]
+    Off,
+    /// Prints a warning, the run goes on as is.
+    Warn,
+    /// Continues in a new thread seeded with a summary of the conversation.
+    Summarize,
+}
+
+/// The instructions file (`instructions_file = "instructions.md"`), or the files
+/// concatenated in order (e.g., `instructions_file = ["base.md", "project.md", "style.md"]`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(super) enum InstructionsFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl InstructionsFiles {
+    pub fn files(&self) -> &[String] {
+        match self {
+            Self::One(file) => std::slice::from_ref(file),
+            Self::Many(files) => files,
+        }
+    }
+}
+
+/// Values of the `{{var}}` placeholders of the instructions (`[instructions.vars]`),
+/// on top of the built-in `{{project_name}}` and `{{date}}`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub(super) struct InstructionsConfig {
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+}
+
+/// Color of each cli role, as a name (e.g., `cyan`) or a 256 colors index (e.g., `45`).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+
+pub(super) struct ThemeConfig {
+    pub accent: Option<String>,
+    pub success: Option<String>,
+    pub warning: Option<String>,
+    pub error: Option<String>,
+}
+
+
+/// Errors on a model not in `KNOWN_MODELS`, with the closest known one as a suggestion.
+fn check_known_model(model: &str) -> Result<()> {
+    if KNOWN_MODELS.contains(&model) {
+        return Ok(());
+    }
+    let closest = KNOWN_MODELS
+        .iter()
+        .min_by_key(|known| edit_distance(model, known))
+        .copied()
+        .unwrap_or_default();
+
+    Err(format!(
+        "Unknown model '{model}', did you mean '{closest}'? (or set 'allow_unknown_model = true')"
+    )
+    .into())
+}
+
+/// Levenshtein distance (chars) between the two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            row.push((prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1));
+        }
+        prev = row;
+    }
+
+    prev[b.len()]
+}
+
+impl Config {
+    /// Checks the values which can not be enforced by the types.
+    pub fn validate(&self) -> Result<()> {
+        let limits = [
+            ("max_prompt_tokens", self.max_prompt_tokens),
+            ("max_completion_tokens", self.max_completion_tokens),
+            ("context_max_tokens", self.context_max_tokens),
+            ("upload_concurrency", self.upload_concurrency),
+        ];
+        for (name, limit) in limits {
+            if limit == Some(0) {
+                return Err(format!("'{name}' must be positive").into());
+            }
+        }
+        // The files are matched by name among the assistant files, so one per bundle.
+        if let Some(template) = &self.bundle_file_name {
+            if !template.contains("{bundle}") {
+                return Err(format!("'bundle_file_name' must contain '{{bundle}}' (got '{template}')").into());
+            }
+        }
+        for tool in self.tools.iter() {
+            ExternalCommand::new(&tool.command).map_err(|err| format!("Tool '{}': {err}", tool.name))?;
+        }
+        for asst in self.asst_configs() {
+            if !self.allow_unknown_model.unwrap_or(false) {
+                check_known_model(&asst.model)?;
+            }
+            for bundle in asst.file_bundles.iter() {
+                for glob in bundle.src_globs.iter().chain(bundle.exclude_globs.iter()) {
+                    Glob::new(glob)
+                        .map_err(|err| format!("Invalid glob '{glob}' in bundle '{}': {err}", bundle.bundle_name))?;
+                }
+                if bundle.chunk_max_bytes == Some(0) {
+                    return Err(format!("'chunk_max_bytes' of '{}' must be positive", bundle.bundle_name).into());
+                }
+                // Builds the transforms to check the patterns and commands.
+                BundleOptions::try_from(bundle)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns all the assistants, the main one first.
+    pub fn asst_configs(&self) -> Vec<AsstConfig> {
+        let main = AsstConfig {
+            name: self.name.clone(),
+            model: self.model.clone(),
+            instructions_file: self.instructions_file.clone(),
+            file_bundles: self.file_bundles.clone(),
+        };
+
+        std::iter::once(main)
+            .chain(self.assistants.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the cli theme, the default colors for the roles not configured.
+    pub fn theme(&self) -> Result<Theme> {
+        let mut theme = Theme::default();
+        let Some(config) = &self.theme else {
+            return Ok(theme);
+        };
+
+        let roles = [
+            (&config.accent, &mut theme.accent),
+            (&config.success, &mut theme.success),
+            (&config.warning, &mut theme.warning),
+            (&config.error, &mut theme.error),
+        ];
+        for (color, role) in roles {
+            if let Some(color) = color {
+                *role = parse_color(color)?;
+            }
+        }
+
+        Ok(theme)
+    }
 }
 
 
 // region --- Froms
 
-impl From<&Config> for asst::CreateConfig {
-    fn from(config: &Config) -> Self {
+impl TryFrom<&FileBundle> for BundleOptions {
+    type Error = Error;
+
+    fn try_from(bundle: &FileBundle) -> Result<Self> {
+        let transforms = bundle
+            .transforms
+            .iter()
+            .map(|transform| -> Result<Box<dyn FileTransform>> {
+                Ok(match transform {
+                    TransformConfig::Redact { patterns, replacement } => {
+                        Box::new(Redact::new(patterns, replacement.as_deref())?)
+                    }
+                    TransformConfig::Command { command } => Box::new(ExternalCommand::new(command)?),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            normalize: bundle.normalize,
+            chunk_max_bytes: bundle.chunk_max_bytes,
+            transforms,
+            max_bytes: bundle.max_bundle_bytes,
+            max_files: bundle.max_files,
+            truncate: bundle.on_limit == BundleLimitAction::Truncate,
+        })
+    }
+}
+
+impl From<&AsstConfig> for asst::CreateConfig {
+    fn from(config: &AsstConfig) -> Self {
         Self {
             name: config.name.clone(),
             model: config.model.clone(),
@@ -35,4 +381,33 @@ impl From<&Config> for asst::CreateConfig {
     }
 }
 
+impl From<&Config> for ClientOptions {
+    fn from(config: &Config) -> Self {
+        Self {
+            request_timeout_secs: config.request_timeout_secs,
+            connect_timeout_secs: config.connect_timeout_secs,
+            retry: config.into(),
+            proxy: config.proxy.clone(),
+            profile: config.profile.clone(),
+            api_key_file: config.api_key_file.as_ref().map(PathBuf::from),
+            // NOTE: The trace is from the cli verbosity, set by the caller.
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&Config> for RetryOptions {
+    fn from(config: &Config) -> Self {
+        let default = RetryOptions::default();
+        Self {
+            max_retries: config.max_retries.unwrap_or(default.max_retries),
+            breaker_threshold: config.breaker_threshold.unwrap_or(default.breaker_threshold),
+            breaker_cooldown: config
+                .breaker_cooldown_secs
+                .map(Duration::from_secs)
+                .unwrap_or(default.breaker_cooldown),
+        }
+    }
+}
+
 // endregion --- Froms
\ No newline at end of file
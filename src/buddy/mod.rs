@@ -2,176 +2,833 @@
 mod config;
 
 use crate::{
-    ais::new_oa_client,
+    ais::{check_connection, check_model, is_read_only, new_oa_client, ClientOptions},
+    events::{append_event, Event},
     utils::{
-        cli::ico_check,
+        cli::{format_utc, ico_check, ico_err, set_theme, show_details, show_status, show_trace},
+        template::render_template,
+        transform::ExternalCommand,
+        watch::watch_paths,
         files::{
-            bundle_to_file, ensure_dir, list_files, load_from_json, load_from_toml, read_to_string,
-            save_to_json,
+            append_to_jsonl, bundle_to_file, ensure_dir, get_glob_set, hash_files_meta, list_files, load_from_json, load_from_jsonl, load_ignore_globs, is_ignored,
+            load_from_toml, load_from_yaml, read_to_string, safer_remove_dir, safer_remove_file, save_to_json, save_to_jsonl, XFile,
         },
     },
-    Result,
+    Error, Result,
 };
 use std::{
+    collections::HashMap,
+    ffi::OsStr,
     fs,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::ais::{
-    asst::{self, AsstId, ThreadId},
+    asst::{self, AsstId, FileId, RunDelta, RunId, RunOptions, ThreadId, ToolCall, ToolHandlers, Usage},
+    msg::{estimate_tokens, get_file_citations, to_chat_msg, AssistantMessage, ChatMsg, Citation, RetrievalInfo},
+    retry::{backoff_delay, is_transient_error},
     OaClient,
 };
-use derive_more::{Deref, From};
+use async_openai::types::{AssistantObject, AssistantTools, MessageObject, MessageRole};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::{stream, Stream, StreamExt};
+use notify::RecommendedWatcher;
+use tokio::{sync::mpsc::UnboundedReceiver, time::sleep};
+use walkdir::WalkDir;
+use derive_more::{Deref, Display, From};
 use serde::{Deserialize, Serialize};
 
-use self::config::Config;
+use self::config::{AsstConfig, Config, ContextGuardrail, FileBundle};
 
 // endregion --- Modules
 
 const BUDDY_TOML: &str = "buddy.toml";
+/// Gitignore-like patterns excluded from all the bundles, relative to their `src_dir` (optional, see `load_ignore_globs`).
+const BUDDY_IGNORE: &str = ".buddyignore";
+const BUDDY_YAML_FILES: &[&str] = &["buddy.yaml", "buddy.yml"];
+const EVENTS_JSONL: &str = "events.jsonl";
+/// Between the instructions files, when several (see `instructions`).
+const INSTRUCTIONS_SEPARATOR: &str = "\n\n---\n\n";
+/// Hash of the sources of each uploaded bundle file, by file name (see `prepare_bundle`).
+const BUNDLE_HASHES_JSON: &str = "bundle-hashes.json";
+/// Where `dry_run` generates the bundles, in the data dir (also the `{asst_id}` of their names).
+const DRY_RUN_DIR: &str = "dry-run";
+const DEFAULT_DATA_DIR: &str = ".buddy";
+const ENV_BUDDY_DATA_DIR: &str = "BUDDY_DATA_DIR";
+/// Model of the runs, instead of the assistant one (e.g., set in the buddy `.env`).
+const ENV_BUDDY_MODEL: &str = "BUDDY_MODEL";
+const DOT_ENV: &str = ".env";
+/// The conversation messages in a snapshot, in its data dir (see `export_snapshot`).
+const SNAPSHOT_TRANSCRIPT: &str = "transcript.jsonl";
+/// The conversation loaded at startup, persisted as `conv.json` (the others as `conv-<name>.json`).
+pub const DEFAULT_CONV_NAME: &str = "default";
+const STARTUP_ATTEMPTS: u32 = 5;
+const ASST_CACHE_TTL: Duration = Duration::from_secs(60);
+const DEFAULT_CONTEXT_MAX_TOKENS: u32 = 100_000;
+/// The recent messages carried over verbatim by the `summarize` guardrail, up to `1 / CONTEXT_RECENT_SHARE`
+/// of `context_max_tokens` (see `split_for_summary`).
+const CONTEXT_RECENT_SHARE: usize = 4;
+const DEFAULT_UPLOAD_CONCURRENCY: u32 = 4;
+const DEFAULT_BUNDLE_FILE_NAME: &str = "{name}-{bundle}-bundle-{asst_id}.{ext}";
+const SUMMARY_PROMPT: &str =
+    "Summarize our conversation so far in a few concise bullet points. Do not add anything else.";
 
 #[derive(Debug)]
 pub struct Buddy {
     dir: PathBuf,
     oac: OaClient,
-    asst_id: AsstId,
     config: Config,
+    /// All the assistants of the buddy, the main one first.
+    asst_configs: Vec<AsstConfig>,
+    /// Index of the active assistant in `asst_configs`.
+    current: usize,
+    /// Id of the active assistant.
+    asst_id: AsstId,
+    /// Bundle names attached to the messages (empty means all).
+    active_bundles: Vec<String>,
+    /// Bundle names which failed in the last upload.
+    failed_bundles: Mutex<Vec<String>>,
+    /// Additional instructions for the next message only (see `set_note`).
+    note: Mutex<Option<String>>,
+    /// End of the session time budget (see `set_session_deadline`).
+    session_deadline: Option<Instant>,
+    /// Last fetched assistant object, reused for `ASST_CACHE_TTL` (see `asst_obj`).
+    asst_cache: Mutex<Option<(Instant, AssistantObject)>>,
+    /// Handlers of the function calls of the runs (see `register_tool`).
+    tool_handlers: ToolHandlers,
+    /// Token usage of the session runs, by model (see `session_usage`).
+    usage_by_model: Mutex<Vec<(String, Usage)>>,
+}
+
+/// Token usage of the session runs of a model.
+#[derive(Debug)]
+pub struct ModelUsage {
+    pub model: String,
+    pub usage: Usage,
+    /// Estimated cost in USD, `None` without a price for the model (`[prices.<model>]`).
+    pub cost: Option<f64>,
+}
+
+/// State of a configured bundle compared to the files attached to the assistant.
+#[derive(Debug, Display)]
+pub enum BundleStatus {
+    #[display("up-to-date")]
+    UpToDate,
+    /// Not attached to the assistant.
+    #[display("missing")]
+    Missing,
+    /// Sources modified after the upload.
+    #[display("stale")]
+    Stale,
+    /// No source files matched (nothing to upload).
+    #[display("no sources")]
+    NoSources,
 }
 
-#[derive(Debug, From, Deref, Deserialize, Serialize)]
+/// A configured bundle and its local bundle file.
+#[derive(Debug)]
+pub struct BundleInfo {
+    pub name: String,
+    /// The bundle file in the data files dir.
+    pub path: PathBuf,
+    /// Size of the bundle file in bytes (0 when not bundled yet).
+    pub size: u64,
+    /// Number of source files currently matched by the bundle.
+    pub file_count: usize,
+}
+
+/// What a bundle would upload, computed without writing anything.
+#[derive(Debug)]
+pub struct BundlePlan {
+    pub name: String,
+    /// Name of the uploaded bundle file.
+    pub file_name: String,
+    /// Source files currently matched by the bundle.
+    pub src_files: Vec<PathBuf>,
+}
+
+/// Overview of the live assistant.
+#[derive(Debug)]
+pub struct AsstInfo {
+    pub name: String,
+    pub model: String,
+    pub tools: Vec<String>,
+    pub instructions_chars: usize,
+    /// Attached files count, or why it could not be listed.
+    pub file_count: Result<usize>,
+}
+
+/// What the model can currently see for a conversation.
+#[derive(Debug)]
+pub struct ConvContext {
+    pub instructions: String,
+    /// Names of the files attached to the assistant.
+    pub file_names: Result<Vec<String>>,
+    /// Number of messages in the conversation thread.
+    pub msg_count: Result<usize>,
+}
+
+/// Result of an upload of the bundles.
+#[derive(Debug, Default)]
+pub struct UploadSummary {
+    pub uploaded: u32,
+    /// Names of the bundles which failed to upload.
+    pub failed: Vec<String>,
+}
+
+/// A bundle file ready to upload (see `prepare_bundle`).
+struct PreparedBundle {
+    file: PathBuf,
+    /// Hash of the sources, stored once uploaded.
+    hash: String,
+    /// The sources changed (or recreate), so uploaded even when already attached.
+    force: bool,
+}
+
+#[derive(Debug, Deref, Deserialize, Serialize)]
 pub struct Conv {
+    #[deref]
     thread_id: ThreadId,
+    /// Where the conversation is persisted (`None` for a scratch one).
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+impl Conv {
+    fn new(thread_id: ThreadId, file: Option<PathBuf>) -> Self {
+        Self { thread_id, file }
+    }
+}
+
+/// A message of the conversation history (see `Buddy::history_entries`).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    /// Message id, the cursor to fetch only the newer messages.
+    id: String,
+    /// Creation time of the message (unix secs), 0 when cached before it was recorded.
+    #[serde(default)]
+    pub ts: u64,
+    #[serde(flatten)]
+    pub msg: ChatMsg,
+}
+
+impl From<MessageObject> for HistoryEntry {
+    fn from(msg: MessageObject) -> Self {
+        Self {
+            id: msg.id.clone(),
+            ts: msg.created_at.max(0) as u64,
+            msg: to_chat_msg(msg),
+        }
+    }
 }
 
 /// Public functions
 impl Buddy {
+    /// Name of the active assistant.
     pub fn name(&self) -> &str {
-        &self.config.name
+        &self.asst_config().name
+    }
+
+    /// Names of all the assistants of the buddy, the main one first.
+    pub fn asst_names(&self) -> Vec<&str> {
+        self.asst_configs.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Returns the effective config serialized back to TOML.
+    pub fn config_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(&self.config)?)
     }
 
-    pub async fn init_form_dir(dir: impl AsRef<Path>, recreate_asst: bool) -> Result<Self> {
-        let dir = dir.as_ref();
+    /// Inits the buddy of the dir. The `profile` overrides the config one.
+    pub async fn init_form_dir(
+        dir: impl AsRef<Path>,
+        recreate_asst: bool,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        let dir = find_buddy_dir(dir.as_ref())?;
 
         // load from directory
-        let config: Config = load_from_toml(dir.join(BUDDY_TOML))?;
+        let config = load_config(&dir)?;
+        Self::init(dir, config, recreate_asst, profile, true).await
+    }
 
-        // Get or create the openAI assistant
-        let oac = new_oa_client()?;
-        let asst_id = asst::load_or_create_asst(&oac, (&config).into(), recreate_asst).await?;
+    /// Like `init_form_dir`, but the assistant is loaded as is, its instructions and files
+    /// not uploaded (e.g., to upload them once with `sync_asst`).
+    pub async fn init_form_dir_no_sync(dir: impl AsRef<Path>, profile: Option<&str>) -> Result<Self> {
+        let dir = find_buddy_dir(dir.as_ref())?;
+
+        let config = load_config(&dir)?;
+        Self::init(dir, config, false, profile, false).await
+    }
+
+    /// Inits the buddy from an explicit config file (e.g., `buddy.dev.toml`).
+    /// The buddy dir (sources, data dir) is the config file parent dir.
+    pub async fn init_from_config(
+        config_file: impl AsRef<Path>,
+        recreate_asst: bool,
+        profile: Option<&str>,
+    ) -> Result<Self> {
+        let config_file = config_file.as_ref();
+        let dir = match config_file.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let config = load_config_file(config_file)?;
+        Self::init(dir, config, recreate_asst, profile, true).await
+    }
+
+    /// Inits the buddy, retrying the startup on transient (network, server) errors.
+    /// With `sync`, the instructions and files of the assistant are uploaded (see `sync_asst`).
+    async fn init(
+        dir: PathBuf,
+        mut config: Config,
+        recreate_asst: bool,
+        profile: Option<&str>,
+        sync: bool,
+    ) -> Result<Self> {
+        if let Some(profile) = profile {
+            config.profile = Some(profile.to_string());
+        }
+        config.validate()?;
+        set_theme(config.theme()?);
+        load_dot_envs(&dir);
+        let mut client_options: ClientOptions = (&config).into();
+        client_options.api_key_file = client_options.api_key_file.map(|file| dir.join(file));
+        client_options.trace = show_trace();
+        let oac = new_oa_client(client_options)?;
+
+        let mut attempt = 1;
+        loop {
+            let res = Self::connect(dir.clone(), config.clone(), oac.clone(), recreate_asst, sync).await;
+            match res {
+                Err(err) if attempt < STARTUP_ATTEMPTS && is_transient_error(&err) => {
+                    println!("{} {err}", ico_err());
+                    attempt += 1;
+                    if show_status() {
+                        println!("Retrying startup ({attempt}/{STARTUP_ATTEMPTS})…");
+                    }
+                    sleep(backoff_delay(attempt)).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    async fn connect(dir: PathBuf, config: Config, oac: OaClient, recreate_asst: bool, sync: bool) -> Result<Self> {
+        let asst_configs = config.asst_configs();
+
+        // Get or create the openAI assistant (the main one)
+        check_connection(&oac).await?;
+        let asst_id = asst::load_or_create_asst(&oac, (&asst_configs[0]).into(), recreate_asst).await?;
 
         // Create buddy
-        let buddy = Buddy {
-            dir: dir.to_path_buf(),
+        let mut buddy = Buddy {
+            dir,
             oac,
-            asst_id,
             config,
+            asst_configs,
+            current: 0,
+            asst_id,
+            active_bundles: Vec::new(),
+            failed_bundles: Mutex::new(Vec::new()),
+            note: Mutex::new(None),
+            asst_cache: Mutex::new(None),
+            session_deadline: None,
+            tool_handlers: ToolHandlers::default(),
+            usage_by_model: Mutex::new(Vec::new()),
         };
+        for tool in buddy.config.tools.clone() {
+            let command = ExternalCommand::new(&tool.command)?;
+            buddy.register_tool(tool.name, move |call| {
+                command.run(&call.arguments, ("BUDDY_TOOL", OsStr::new(&call.name)))
+            });
+        }
+        buddy.health_check().await?;
+        // NOTE: Read-only (e.g., inspect) loads the assistant as is, no uploads.
+        if sync && !is_read_only() {
+            buddy.sync_asst(false).await?;
+        }
 
-        // Upload the instructions
-        buddy.upload_instructions().await?;
+        Ok(buddy)
+    }
 
-        // Upload the file
-        buddy.upload_files(false).await?;
+    /// Registers the handler of the `name` function calls of the runs (the function itself
+    /// being declared on the assistant). Its output, or error, is submitted as the call output.
+    pub fn register_tool(
+        &mut self,
+        name: impl Into<String>,
+        handler: impl Fn(ToolCall) -> Result<String> + Send + Sync + 'static,
+    ) {
+        self.tool_handlers.insert(name, Arc::new(handler));
+    }
 
-        Ok(buddy)
+    /// Checks that the API key works, the assistant is reachable, and its model available.
+    pub async fn health_check(&self) -> Result<()> {
+        // NOTE: Transient errors are kept as is, so the startup can retry them.
+        let context = |what: String| {
+            move |err: Error| -> Error {
+                if is_transient_error(&err) {
+                    err
+                } else {
+                    format!("Health check failed, {what}: {err}").into()
+                }
+            }
+        };
+        let asst_obj = self
+            .asst_obj()
+            .await
+            .map_err(context(format!("assistant '{}'", self.name())))?;
+        check_model(&self.oac, &asst_obj.model)
+            .await
+            .map_err(context(format!("model '{}'", asst_obj.model)))?;
+
+        let model = &self.asst_config().model;
+        if &asst_obj.model != model {
+            println!(
+                "{} Assistant '{}' uses the model '{}', not the configured '{model}'",
+                ico_err(),
+                self.name(),
+                asst_obj.model
+            );
+        }
+        if show_status() {
+            println!("{} Health check ok", ico_check());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the overview of the live assistant (read only).
+    ///
+    /// A failure to list the files does not fail the overview.
+    pub async fn info(&self) -> Result<AsstInfo> {
+        let asst_obj = self.asst_obj().await?;
+        let tools = asst_obj
+            .tools
+            .iter()
+            .map(|tool| match tool {
+                AssistantTools::Code(tool) => tool.r#type.clone(),
+                AssistantTools::Retrieval(tool) => tool.r#type.clone(),
+                AssistantTools::Function(tool) => format!("function:{}", tool.function.name),
+            })
+            .collect();
+        let file_count = asst::get_file_hashmap(&self.oac, &self.asst_id)
+            .await
+            .map(|files| files.len());
+
+        Ok(AsstInfo {
+            name: asst_obj.name.unwrap_or_else(|| self.name().to_string()),
+            model: asst_obj.model,
+            tools,
+            instructions_chars: asst_obj.instructions.map(|i| i.chars().count()).unwrap_or(0),
+            file_count,
+        })
+    }
+
+    /// Returns what the model sees for the conversation (read only):
+    /// the instructions, the attached files, and the thread messages count.
+    pub async fn context(&self, conv: &Conv) -> Result<ConvContext> {
+        let asst_obj = self.asst_obj().await?;
+        let file_names = asst::get_file_hashmap(&self.oac, &self.asst_id).await.map(|files| {
+            let mut names: Vec<String> = files.into_keys().collect();
+            names.sort();
+            names
+        });
+        let msg_count = self.history(conv).await.map(|msgs| msgs.len());
+
+        Ok(ConvContext {
+            instructions: asst_obj.instructions.unwrap_or_default(),
+            file_names,
+            msg_count,
+        })
+    }
+
+    /// Exports the conversation messages, oldest first, as chat completions json lines
+    /// (`{"role": ..., "content": ...}`). Returns the number of messages.
+    pub async fn export_thread_jsonl(&self, conv: &Conv, file: &Path) -> Result<usize> {
+        let msgs = self.history(conv).await?;
+        save_to_jsonl(file, &msgs)?;
+
+        Ok(msgs.len())
+    }
+
+    /// Writes a snapshot of the buddy into a `.tar.gz` archive, to be restored with `import_snapshot`:
+    /// the effective config as `buddy.toml`, the data dir (bundle files, conversations, events),
+    /// and the messages of the conversation as `transcript.jsonl` in the data dir.
+    ///
+    /// NOTE: The server-side state (assistant, uploaded files, threads) is not in the snapshot.
+    pub async fn export_snapshot(&self, conv: &Conv, file: &Path) -> Result<()> {
+        let mut transcript = String::new();
+        for msg in self.history(conv).await? {
+            transcript.push_str(&serde_json::to_string(&msg)?);
+            transcript.push('\n');
+        }
+
+        let data_dir = self.data_dir()?;
+        // NOTE: An absolute data dir (outside the buddy dir) is restored as the default one.
+        let data_dir_name = data_dir.strip_prefix(&self.dir).unwrap_or(Path::new(DEFAULT_DATA_DIR));
+
+        write_snapshot(file, &self.config_toml()?, &data_dir, data_dir_name, &transcript)
+    }
+
+    /// Returns the last `exchanges` user/assistant exchanges of the conversation, oldest first
+    /// (empty for a new conversation).
+    pub async fn last_exchanges(&self, conv: &Conv, exchanges: usize) -> Result<Vec<ChatMsg>> {
+        let msgs = self.history(conv).await?;
+        let skip = msgs.len().saturating_sub(exchanges * 2);
+
+        Ok(msgs.into_iter().skip(skip).collect())
+    }
+
+    /// Returns the messages of the conversation, oldest first (see `history_entries`).
+    pub async fn history(&self, conv: &Conv) -> Result<Vec<ChatMsg>> {
+        let entries = self.history_entries(conv).await?;
+
+        Ok(entries.into_iter().map(|entry| entry.msg).collect())
+    }
+
+    /// Returns the messages of the conversation with their time, oldest first.
+    ///
+    /// Read from the local history (`<data_dir>/convs/<thread_id>.history.jsonl`), with only
+    /// the newer messages fetched from the server and appended to it (after each exchange too).
+    /// When they can not be fetched (e.g., offline, thread deleted), the local history is replayed as is.
+    /// A scratch conversation has no local history, all its messages are fetched.
+    pub async fn history_entries(&self, conv: &Conv) -> Result<Vec<HistoryEntry>> {
+        let Some(history_file) = self.history_file(conv)? else {
+            let msgs = asst::list_thread_msgs(&self.oac, &conv.thread_id).await?;
+            return Ok(msgs.into_iter().map(HistoryEntry::from).collect());
+        };
+
+        let mut entries: Vec<HistoryEntry> = if history_file.exists() {
+            load_from_jsonl(&history_file)?
+        } else {
+            Vec::new()
+        };
+        let after = entries.last().map(|entry| entry.id.clone());
+        let msgs = match asst::list_thread_msgs_after(&self.oac, &conv.thread_id, after).await {
+            Ok(msgs) => msgs,
+            Err(err) if !entries.is_empty() => {
+                println!("{} Can't fetch the new messages, local history only: {err}", ico_err());
+                Vec::new()
+            }
+            Err(err) => return Err(err),
+        };
+        for msg in msgs {
+            let entry = HistoryEntry::from(msg);
+            append_to_jsonl(&history_file, &entry)?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    /// Refetches all the messages of the conversation and rewrites its local history,
+    /// to catch the messages changed out-of-band (e.g., from another client).
+    pub async fn sync_history(&self, conv: &Conv) -> Result<Vec<HistoryEntry>> {
+        let entries: Vec<HistoryEntry> = asst::list_thread_msgs(&self.oac, &conv.thread_id)
+            .await?
+            .into_iter()
+            .map(HistoryEntry::from)
+            .collect();
+        if let Some(history_file) = self.history_file(conv)? {
+            save_to_jsonl(&history_file, &entries)?;
+        }
+
+        Ok(entries)
+    }
+
+    /// True when the last exchanges should be printed at launch (see `last_exchanges`).
+    pub fn resume(&self) -> bool {
+        self.config.resume.unwrap_or(false)
+    }
+
+    /// Deletes the active assistant (and its files unless `keep_files`), then its local data dir.
+    ///
+    /// NOTE: For the main assistant, this is the whole data dir.
+    pub async fn destroy(self, keep_files: bool) -> Result<()> {
+        asst::delete(&self.oac, &self.asst_id, keep_files).await?;
+        if show_status() {
+            println!("{} Assistant '{}' deleted", ico_check(), self.name());
+        }
+
+        let data_dir = self.asst_data_dir()?;
+        safer_remove_dir(&data_dir, &self.dir)?;
+        if show_status() {
+            println!("{} Data dir '{}' removed", ico_check(), data_dir.display());
+        }
+
+        Ok(())
+    }
+
+    /// The buddy dir, where its config was found.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Switches to the `name` assistant (loaded or created, then its instructions and files uploaded).
+    ///
+    /// NOTE: Each assistant has its own conversation, to be reloaded with `load_or_create_conv`.
+    pub async fn switch_asst(&mut self, name: &str) -> Result<()> {
+        let Some(idx) = self.asst_configs.iter().position(|c| c.name == name) else {
+            return Err(format!(
+                "No assistant named '{name}' (available: {})",
+                self.asst_names().join(", ")
+            )
+            .into());
+        };
+        if idx == self.current {
+            return Ok(());
+        }
+
+        let asst_config = &self.asst_configs[idx];
+        self.asst_id = asst::load_or_create_asst(&self.oac, asst_config.into(), false).await?;
+        self.current = idx;
+        self.invalidate_asst_cache();
+        self.active_bundles.clear();
+        if let Ok(mut failed) = self.failed_bundles.lock() {
+            failed.clear();
+        }
+
+        self.sync_asst(false).await?;
+
+        Ok(())
+    }
+
+    /// The instructions as uploaded: the instructions files concatenated, with the configured
+    /// prefix and suffix, and their `{{var}}` placeholders filled (see `instructions_vars`).
+    /// `None` when there is no instructions file.
+    pub fn instructions(&self) -> Result<Option<String>> {
+        let files = self.instructions_files();
+        if !files.iter().any(|file| file.exists()) {
+            return Ok(None);
+        }
+
+        let mut contents = Vec::new();
+        for file in files.iter() {
+            if !file.exists() {
+                return Err(format!("Instructions file '{}' not found", file.display()).into());
+            }
+            contents.push(read_to_string(file)?);
+        }
+        let inst_content = contents.join(INSTRUCTIONS_SEPARATOR);
+        let inst_content = [
+            self.config.instruction_prefix.as_deref(),
+            Some(inst_content.as_str()),
+            self.config.instruction_suffix.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n\n");
+        let inst_content = render_template(&inst_content, &self.instructions_vars())
+            .map_err(|err| format!("Can't render the instructions: {err} (see [instructions.vars] of {BUDDY_TOML})"))?;
+
+        Ok(Some(inst_content))
+    }
+
+    /// The instructions files of the assistant, in order.
+    fn instructions_files(&self) -> Vec<PathBuf> {
+        self.asst_config().instructions_file.files().iter().map(|file| self.dir.join(file)).collect()
+    }
+
+    /// The built-in vars of the instructions (`project_name`, the buddy name, and `date`, today in UTC),
+    /// overridden by the configured ones.
+    fn instructions_vars(&self) -> HashMap<String, String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let date = format_utc(now).chars().take(10).collect();
+        let mut vars = HashMap::from([
+            ("project_name".to_string(), self.config.name.clone()),
+            ("date".to_string(), date),
+        ]);
+        if let Some(instructions) = &self.config.instructions {
+            vars.extend(instructions.vars.clone());
+        }
+
+        vars
     }
 
     pub async fn upload_instructions(&self) -> Result<bool> {
-        let file = self.dir.join(&self.config.instructions_file);
-        if file.exists() {
-            let inst_content = read_to_string(&file)?;
+        if let Some(inst_content) = self.instructions()? {
             asst::upload_instructions(&self.oac, &self.asst_id, inst_content).await?;
-            println!("{} Instructions uploaded", ico_check());
+            self.invalidate_asst_cache();
+            if show_status() {
+                println!("{} Instructions uploaded", ico_check());
+            }
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
-    pub async fn upload_files(&self, recreate: bool) -> Result<u32> {
-        let mut num_uploaded = 0;
-
+    /// Bundles and uploads all the configured bundles.
+    ///
+    /// A failing bundle does not stop the others, its name is returned in the summary
+    /// and kept for `retry_failed_uploads`.
+    ///
+    /// In dry-run mode, the bundles are generated, and their files and sizes printed, but not uploaded.
+    pub async fn upload_files(&self, recreate: bool) -> Result<UploadSummary> {
         // the .buddy/files
         let data_files_dir = self.data_files_dir()?;
 
-        // Clean the .buddy/files left over.
-        let exclude_element = format!("*{}*", &self.asst_id);
-        for file in list_files(
-            &data_files_dir,
-            Some(&["*.rs", "*.md"]),
-            Some(&[&exclude_element]),
-        )? {
-            // delete file
-            let file_str = file.to_string_lossy();
-            // Safeguard
-            if !file_str.contains(".buddy") {
-                return Err(format!("Error should no delete: '{}'", file_str).into());
+        // Clean the .buddy/files left over (not a current bundle file, e.g., previous assistant or naming).
+        // NOTE: Matched by the expected names, since the naming might not include the asst id.
+        let bundle_file_names: Vec<String> = self
+            .asst_config()
+            .file_bundles
+            .iter()
+            .map(|b| self.bundle_file_name(b))
+            .collect();
+        for file in list_files(&data_files_dir, None, None, None)? {
+            if bundle_file_names.iter().any(|name| name == file.x_file_name()) {
+                continue;
             }
-            fs::remove_file(&file)?;
+            // delete file (only if really under the data files dir)
+            safer_remove_file(&file, &data_files_dir)?;
         }
 
         // Genrate and upload the .buddy/files bundle files
-        for bundle in self.config.file_bundles.iter() {
-            let src_dir = self.dir.join(&bundle.src_dir);
+        let bundles: Vec<&FileBundle> = self.asst_config().file_bundles.iter().collect();
+        self.warn_empty_bundles(&bundles);
+        self.upload_bundles(&bundles, recreate).await
+    }
+
+    /// Returns the status of each configured bundle, without bundling or uploading.
+    ///
+    /// A bundle is stale when one of its source files was modified after
+    /// the attached file was uploaded.
+    pub async fn status(&self) -> Result<Vec<(String, BundleStatus)>> {
+        let org_files = asst::get_asst_org_files(&self.oac, &self.asst_id).await?;
+
+        let mut statuses = Vec::new();
+        for bundle in self.asst_config().file_bundles.iter() {
+            let files = self.bundle_src_files(bundle)?;
+
+            let bundle_file_name = self.bundle_file_name(bundle);
+            let org_file = org_files.iter().find(|f| f.filename == bundle_file_name);
+
+            let status = match org_file {
+                _ if files.is_empty() => BundleStatus::NoSources,
+                None => BundleStatus::Missing,
+                Some(org_file) => {
+                    let uploaded_at = UNIX_EPOCH + Duration::from_secs(org_file.created_at as u64);
+                    let modified_after = files.iter().any(|file| {
+                        file.metadata()
+                            .and_then(|m| m.modified())
+                            .map(|modified| modified > uploaded_at)
+                            .unwrap_or(true)
+                    });
+                    if modified_after {
+                        BundleStatus::Stale
+                    } else {
+                        BundleStatus::UpToDate
+                    }
+                }
+            };
+            statuses.push((bundle.bundle_name.clone(), status));
+        }
 
-            if src_dir.is_dir() {
-                let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+        Ok(statuses)
+    }
 
-                let files = list_files(&src_dir, Some(&src_globs), None)?;
+    /// Returns the bundles of the active assistant with their local bundle file.
+    pub fn bundle_files(&self) -> Result<Vec<BundleInfo>> {
+        let data_files_dir = self.data_files_dir()?;
 
-                if !files.is_empty() {
-                    // Compute bundle file name.
-                    let bundle_file_name = format!(
-                        "{}-{}-bundle-{}.{}",
-                        self.name(),
-                        bundle.bundle_name,
-                        self.asst_id,
-                        bundle.dst_ext
-                    );
+        self.asst_config()
+            .file_bundles
+            .iter()
+            .map(|bundle| {
+                let path = data_files_dir.join(self.bundle_file_name(bundle));
+                let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                Ok(BundleInfo {
+                    name: bundle.bundle_name.clone(),
+                    path,
+                    size,
+                    file_count: self.bundle_src_files(bundle)?.len(),
+                })
+            })
+            .collect()
+    }
 
-                    let bundle_file = self.data_files_dir()?.join(bundle_file_name);
+    /// Returns what each configured bundle would upload (no bundling, no data dir).
+    pub fn bundle_plan(&self) -> Result<Vec<BundlePlan>> {
+        self.asst_config()
+            .file_bundles
+            .iter()
+            .map(|bundle| {
+                Ok(BundlePlan {
+                    name: bundle.bundle_name.clone(),
+                    file_name: self.bundle_file_name(bundle),
+                    src_files: self.bundle_src_files(bundle)?,
+                })
+            })
+            .collect()
+    }
 
-                    // If it does not exist, then we will force a reupload.
-                    let force_reupload = recreate || !bundle_file.exists();
+    /// Reattempts the upload of the bundles that failed in the last upload.
+    pub async fn retry_failed_uploads(&self) -> Result<UploadSummary> {
+        let failed = self.failed_bundles();
+        let bundles: Vec<&FileBundle> = self
+            .asst_config()
+            .file_bundles
+            .iter()
+            .filter(|b| failed.contains(&b.bundle_name))
+            .collect();
 
-                    // Rebundle no matter if exist or not (to check)
-                    bundle_to_file(files, &bundle_file)?;
+        self.upload_bundles(&bundles, true).await
+    }
 
-                    // Upload
-                    let (_, uploaded) = asst::upload_file_by_name(
-                        &self.oac,
-                        &self.asst_id,
-                        &bundle_file,
-                        force_reupload,
-                    )
-                    .await?;
+    /// Re-attaches the bundle files still in the account but not attached to the assistant.
+    /// Returns the re-attached file names.
+    pub async fn reattach_files(&self) -> Result<Vec<String>> {
+        let bundle_file_names: Vec<String> = self
+            .asst_config()
+            .file_bundles
+            .iter()
+            .map(|b| self.bundle_file_name(b))
+            .collect();
 
-                    if uploaded {
-                        num_uploaded += 1;
-                    }
-                }
-            }
+        let reattached = asst::reattach_files(&self.oac, &self.asst_id, &bundle_file_names).await?;
+        if !reattached.is_empty() {
+            self.invalidate_asst_cache();
         }
 
-        Ok(num_uploaded)
+        Ok(reattached)
+    }
+
+    /// The bundle names which failed in the last upload.
+    pub fn failed_bundles(&self) -> Vec<String> {
+        self.failed_bundles
+            .lock()
+            .map(|failed| failed.clone())
+            .unwrap_or_default()
     }
 
-    pub async fn load_or_create_conv(&self, recreate: bool) -> Result<Conv> {
-        let conv_file = self.data_dir()?.join("conv.json");
+    /// Loads the `conv_name` conversation of the assistant, or creates it (always when `recreate`).
+    pub async fn load_or_create_conv(&self, conv_name: &str, recreate: bool) -> Result<Conv> {
+        let conv_file = self.conv_file(conv_name)?;
 
         if recreate && conv_file.exists() {
             let _ = fs::remove_file(&conv_file);
         }
-        let conv = if let Ok(conv) = load_from_json::<Conv>(&conv_file) {
+        let conv = if let Ok(mut conv) = load_from_json::<Conv>(&conv_file) {
+            conv.file = Some(conv_file);
             asst::get_thread(&self.oac, &conv.thread_id)
                 .await
-                .map_err(|_| format!("Connot find thread_id for {:?}", conv))?;
-            println!("{} Conversation loaded", ico_check());
+                .map_err(|_| Error::ThreadNotFound(conv.thread_id.to_string()))?;
+            if show_status() {
+                println!("{} Conversation loaded", ico_check());
+            }
             conv
         } else {
             let thread_id = asst::create_thread(&self.oac).await?;
-            println!("{} Conversation created", ico_check());
-            let conv = thread_id.into();
+            if show_status() {
+                println!("{} Conversation created", ico_check());
+            }
+            let conv = Conv::new(thread_id, Some(conv_file.clone()));
             save_to_json(&conv_file, &conv)?;
             conv
         };
@@ -179,24 +836,1143 @@ impl Buddy {
         Ok(conv)
     }
 
-    pub async fn chat(&self, conv: &Conv, msg: &str) -> Result<String> {
-        let res = asst::run_thread_msg(&self.oac, &self.asst_id, &conv.thread_id, msg).await?;
+    /// Names of the persisted conversations of the assistant, sorted.
+    pub fn list_convs(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(self.asst_data_dir()?)? {
+            let file_name = entry?.file_name();
+            let file_name = file_name.to_string_lossy();
+            if file_name == "conv.json" {
+                names.push(DEFAULT_CONV_NAME.to_string());
+            } else if let Some(name) = file_name.strip_prefix("conv-").and_then(|n| n.strip_suffix(".json")) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Whether the `conv_name` conversation is persisted for the assistant.
+    pub fn has_conv(&self, conv_name: &str) -> Result<bool> {
+        Ok(self.conv_file(conv_name)?.exists())
+    }
+
+    /// File of the `conv_name` conversation in the assistant data dir,
+    /// `conv.json` for the default one, `conv-<name>.json` otherwise.
+    fn conv_file(&self, conv_name: &str) -> Result<PathBuf> {
+        if conv_name.is_empty() || !conv_name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid conversation name '{conv_name}'").into());
+        }
+        let file_name = if conv_name == DEFAULT_CONV_NAME {
+            "conv.json".to_string()
+        } else {
+            format!("conv-{conv_name}.json")
+        };
+
+        Ok(self.asst_data_dir()?.join(file_name))
+    }
+
+    /// Creates a scratch conversation, a new thread which is never persisted (no `conv.json`).
+    pub async fn create_scratch_conv(&self) -> Result<Conv> {
+        let thread_id = asst::create_thread(&self.oac).await?;
+        if show_status() {
+            println!("{} Scratch conversation created", ico_check());
+        }
+
+        Ok(Conv::new(thread_id, None))
+    }
+
+    /// Deletes the thread of the conversation (e.g., a scratch conversation at quit).
+    pub async fn delete_conv(&self, conv: Conv) -> Result<()> {
+        asst::delete_thread(&self.oac, &conv.thread_id).await
+    }
+
+    /// Forks the conversation into a new thread seeded with its first `upto` messages,
+    /// and persists it as the `name` conversation.
+    ///
+    /// NOTE: Only user messages can be added to a thread, so the assistant answers
+    ///       are replayed as user messages prefixed with `[assistant answer]`.
+    ///       Non-text content (e.g., images) is omitted.
+    pub async fn fork_conv(&self, conv: &Conv, upto: usize, name: &str) -> Result<Conv> {
+        let fork_file = self.conv_file(name)?;
+        let thread_id = self.seed_thread(conv, upto).await?;
+
+        let fork = Conv::new(thread_id, Some(fork_file.clone()));
+        save_to_json(&fork_file, &fork)?;
+
+        Ok(fork)
+    }
+
+    /// Returns a summary of the conversation in a few bullet points.
+    ///
+    /// The summary runs on a throwaway copy of the thread (see `fork_conv`),
+    /// so it does not become part of the conversation context.
+    pub async fn summarize_conv(&self, conv: &Conv) -> Result<String> {
+        let msgs = self.history(conv).await?;
+        self.summarize_msgs(msgs).await
+    }
+
+    /// Returns a summary of the messages, run on a throwaway thread seeded with them.
+    async fn summarize_msgs(&self, msgs: Vec<ChatMsg>) -> Result<String> {
+        let thread_id = self.seed_thread_msgs(msgs).await?;
+
+        let res = asst::run_thread_msg(
+            &self.oac,
+            &self.asst_id,
+            &thread_id,
+            SUMMARY_PROMPT,
+            None,
+            &self.run_options(),
+            &self.images_dir()?,
+        )
+        .await;
+
+        // Best-effort cleanup of the throwaway thread.
+        let _ = asst::delete_thread(&self.oac, &thread_id).await;
+
+        Ok(res?.text)
+    }
+
+    /// Returns the paths to watch for the auto refresh (bundle src dirs and instructions files).
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        self.asst_config()
+            .file_bundles
+            .iter()
+            .map(|b| self.dir.join(&b.src_dir))
+            .chain(self.instructions_files())
+            .filter(|p| p.exists())
+            .filter_map(|p| p.canonicalize().ok())
+            .collect()
+    }
+
+    /// Starts watching the sources (see `watch_paths`), the changes being sent debounced by `debounce`,
+    /// to be given to `refresh_changed`.
+    ///
+    /// NOTE: The returned watcher must be kept alive for the changes to flow.
+    pub fn watch(&self, debounce: Duration) -> Result<(RecommendedWatcher, UnboundedReceiver<Vec<PathBuf>>)> {
+        watch_paths(&self.watch_paths(), debounce)
+    }
+
+    /// Re-uploads the instructions and/or the files impacted by the `changed` paths.
+    /// Only the changed bundles are re-uploaded (see the bundle hashes).
+    pub async fn refresh_changed(&self, changed: &[PathBuf]) -> Result<()> {
+        let data_dir = self.data_dir()?.canonicalize()?;
+        let changed: Vec<&PathBuf> = changed.iter().filter(|p| !p.starts_with(&data_dir)).collect();
+
+        let inst_files: Vec<PathBuf> =
+            self.instructions_files().into_iter().filter_map(|file| file.canonicalize().ok()).collect();
+        if changed.iter().any(|p| inst_files.contains(p)) {
+            self.upload_instructions().await?;
+        }
+
+        // NOTE: The changed paths are canonical, so matched against the canonical src dirs.
+        let ignore_globs = load_ignore_globs(&self.dir.join(BUDDY_IGNORE))?;
+
+        let mut bundle_changed = false;
+        for bundle in self.asst_config().file_bundles.iter() {
+            let Ok(src_dir) = self.dir.join(&bundle.src_dir).canonicalize() else {
+                continue;
+            };
+            let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+            let globs = get_glob_set(&src_globs)?;
+            let exclude_globs: Vec<&str> = bundle.exclude_globs.iter().map(AsRef::as_ref).collect();
+            let exclude_globs = get_glob_set(&exclude_globs)?;
+            if changed
+                .iter()
+                .any(|p| {
+                    p.starts_with(&src_dir)
+                        && globs.is_match(p)
+                        && !exclude_globs.is_match(p)
+                        && !is_ignored(ignore_globs.as_ref(), &src_dir, p)
+                })
+            {
+                bundle_changed = true;
+                break;
+            }
+        }
+        if bundle_changed {
+            self.upload_files(false).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Selects the bundles attached to the subsequent messages.
+    /// An empty list restores the default (all configured bundles).
+    pub fn use_bundles(&mut self, bundle_names: Vec<String>) -> Result<()> {
+        for name in bundle_names.iter() {
+            if !self.asst_config().file_bundles.iter().any(|b| &b.bundle_name == name) {
+                return Err(format!("No bundle named '{name}' in {BUDDY_TOML}").into());
+            }
+        }
+        self.active_bundles = bundle_names;
+
+        Ok(())
+    }
+
+    pub fn active_bundles(&self) -> &[String] {
+        &self.active_bundles
+    }
+
+    /// Sets the end of the session time budget, the runs still in progress then are cancelled.
+    pub fn set_session_deadline(&mut self, deadline: Instant) {
+        self.session_deadline = Some(deadline);
+    }
+
+    pub fn session_deadline(&self) -> Option<Instant> {
+        self.session_deadline
+    }
+
+    pub fn session_expired(&self) -> bool {
+        self.session_deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Sets additional instructions for the next message only
+    /// (appended to the assistant instructions for that run).
+    pub fn set_note(&self, note: String) -> Result<()> {
+        *self.note.lock().map_err(|_| "Note lock poisoned")? = Some(note);
+        Ok(())
+    }
+
+    /// Sends the message and returns the answer.
+    ///
+    /// Before the run, the context guardrail (see `guard_context`) might switch `conv`
+    /// to a summarized continuation.
+    ///
+    /// The `model` overrides the assistant model for this message only. An unavailable
+    /// model is reported, and the assistant model used instead.
+    pub async fn chat(&self, conv: &mut Conv, msg: &str, model: Option<&str>) -> Result<AssistantMessage> {
+        self.run_chat(conv, msg, None, model).await
+    }
+
+    /// Sends the message, and returns the text deltas of the answer as they are generated,
+    /// then the completed run (see `asst::run_thread_msg_stream`).
+    ///
+    /// Like `chat`, the usage and the run events are recorded (not the model override).
+    pub async fn chat_stream(&self, conv: &mut Conv, msg: &str) -> Result<impl Stream<Item = Result<RunDelta>> + '_> {
+        self.guard_context(conv).await?;
+
+        self.log_event(Event::MsgSent {
+            thread_id: conv.thread_id.to_string(),
+            chars: msg.chars().count(),
+        });
+
+        // The note is for this message only.
+        let mut options = self.run_options();
+        options.additional_instructions = self.note.lock().ok().and_then(|mut note| note.take());
+        let file_ids = self.active_file_ids().await?;
+        let deltas =
+            asst::run_thread_msg_stream(&self.oac, &self.asst_id, &conv.thread_id, msg, file_ids, &options).await;
+        let deltas = match deltas {
+            Ok(deltas) => deltas,
+            Err(err) => {
+                self.log_event(Event::Error {
+                    message: err.to_string(),
+                });
+                return Err(err);
+            }
+        };
+
+        let model = self.run_model(&options);
+        let thread_id = conv.thread_id.to_string();
+        Ok(deltas.inspect(move |delta| match delta {
+            Ok(RunDelta::Text(_)) => (),
+            Ok(RunDelta::Completed { run_id, usage, .. }) => {
+                if let Some(usage) = usage {
+                    self.add_usage(&model, usage);
+                }
+                self.log_event(Event::RunCompleted {
+                    thread_id: thread_id.clone(),
+                    run_id: run_id.to_string(),
+                    usage: usage.clone(),
+                });
+            }
+            Err(err) => self.log_event(Event::Error {
+                message: err.to_string(),
+            }),
+        }))
+    }
+
+    /// The retrieval diagnostic of a run of the conversation (e.g., after `chat_stream`).
+    pub async fn run_retrieval(&self, conv: &Conv, run_id: &RunId, citations: usize) -> Result<RetrievalInfo> {
+        let calls = asst::count_retrieval_calls(&self.oac, &conv.thread_id, run_id).await?;
+
+        Ok(RetrievalInfo { calls, citations })
+    }
+
+    /// Token usage of the session runs by model, with its estimated cost (see `ModelUsage`).
+    pub fn session_usage(&self) -> Vec<ModelUsage> {
+        let usage_by_model = self.usage_by_model.lock().map(|usages| usages.clone()).unwrap_or_default();
 
-        Ok(res)
+        usage_by_model
+            .into_iter()
+            .map(|(model, usage)| {
+                let cost = self.config.prices.get(&model).map(|price| {
+                    (usage.prompt_tokens as f64 * price.prompt + usage.completion_tokens as f64 * price.completion)
+                        / 1_000_000.0
+                });
+                ModelUsage { model, usage, cost }
+            })
+            .collect()
+    }
+
+    /// Cancels the run of the conversation still in progress (e.g., a `chat_stream` interrupted).
+    pub async fn cancel_run(&self, conv: &Conv) -> Result<()> {
+        asst::cancel_last_run(&self.oac, &conv.thread_id).await
+    }
+
+    /// The file citations of the last answer of the conversation (e.g., after `chat_stream`).
+    pub async fn last_answer_citations(&self, conv: &Conv) -> Result<Vec<Citation>> {
+        let msg = asst::get_first_thread_msg(&self.oac, &conv.thread_id).await?;
+
+        Ok(msg
+            .filter(|msg| matches!(msg.role, MessageRole::Assistant))
+            .map(|msg| get_file_citations(&msg))
+            .unwrap_or_default())
+    }
+
+    /// The names of the files cited with their quotes, once each in citation order
+    /// (the file id when not attached to the assistant anymore).
+    pub async fn cited_files(&self, citations: &[Citation]) -> Result<Vec<(String, Vec<String>)>> {
+        let name_by_id: HashMap<String, String> = asst::get_file_hashmap(&self.oac, &self.asst_id)
+            .await?
+            .into_iter()
+            .map(|(name, id)| (id.to_string(), name))
+            .collect();
+
+        let mut files: Vec<(String, Vec<String>)> = Vec::new();
+        for citation in citations {
+            let name = name_by_id.get(&citation.file_id).unwrap_or(&citation.file_id);
+            let idx = match files.iter().position(|(n, _)| n == name) {
+                Some(idx) => idx,
+                None => {
+                    files.push((name.clone(), Vec::new()));
+                    files.len() - 1
+                }
+            };
+            if !citation.quote.is_empty() {
+                files[idx].1.push(citation.quote.clone());
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Sends the message with the `image` attached (for the vision models), and returns the answer.
+    pub async fn chat_image(&self, conv: &mut Conv, image: &Path, msg: &str) -> Result<AssistantMessage> {
+        self.run_chat(conv, msg, Some(image), None).await
     }
 }
 
 /// Private functions
 impl Buddy {
+    async fn run_chat(
+        &self,
+        conv: &mut Conv,
+        msg: &str,
+        image: Option<&Path>,
+        model: Option<&str>,
+    ) -> Result<AssistantMessage> {
+        self.guard_context(conv).await?;
+
+        let model = match model {
+            Some(model) => match check_model(&self.oac, model).await {
+                Ok(()) => Some(model.to_string()),
+                Err(err) => {
+                    println!("{} Model '{model}' not usable, using the assistant model: {err}", ico_err());
+                    None
+                }
+            },
+            None => None,
+        };
+
+        self.log_event(Event::MsgSent {
+            thread_id: conv.thread_id.to_string(),
+            chars: msg.chars().count(),
+        });
+
+        // The note is for this message only.
+        let mut options = self.run_options();
+        options.additional_instructions = self.note.lock().ok().and_then(|mut note| note.take());
+        if model.is_some() {
+            options.model = model;
+        }
+        let images_dir = self.images_dir()?;
+        let res = match image {
+            Some(image) => {
+                asst::run_thread_image_msg(
+                    &self.oac,
+                    &self.asst_id,
+                    &conv.thread_id,
+                    msg,
+                    image,
+                    &options,
+                    &images_dir,
+                )
+                .await
+            }
+            None => {
+                let file_ids = self.active_file_ids().await?;
+                asst::run_thread_msg(
+                    &self.oac,
+                    &self.asst_id,
+                    &conv.thread_id,
+                    msg,
+                    file_ids,
+                    &options,
+                    &images_dir,
+                )
+                .await
+            }
+        };
+
+        match res {
+            Ok(asst_msg) => {
+                if let Some(usage) = &asst_msg.usage {
+                    self.add_usage(&self.run_model(&options), usage);
+                }
+                self.log_event(Event::RunCompleted {
+                    thread_id: conv.thread_id.to_string(),
+                    run_id: asst_msg.run_id.to_string(),
+                    usage: asst_msg.usage.clone(),
+                });
+                // Best-effort, the history cache catches up at its next read otherwise.
+                let _ = self.history(conv).await;
+                Ok(asst_msg)
+            }
+            Err(err) => {
+                self.log_event(Event::Error {
+                    message: err.to_string(),
+                });
+                Err(err)
+            }
+        }
+    }
+
+    fn asst_config(&self) -> &AsstConfig {
+        &self.asst_configs[self.current]
+    }
+
+    /// Returns the assistant object, from the cache when fetched less than `ASST_CACHE_TTL` ago.
+    async fn asst_obj(&self) -> Result<AssistantObject> {
+        if let Ok(cache) = self.asst_cache.lock() {
+            if let Some((fetched_at, asst_obj)) = cache.as_ref() {
+                if fetched_at.elapsed() < ASST_CACHE_TTL {
+                    return Ok(asst_obj.clone());
+                }
+            }
+        }
+
+        let asst_obj = asst::get_asst(&self.oac, &self.asst_id).await?;
+        if let Ok(mut cache) = self.asst_cache.lock() {
+            *cache = Some((Instant::now(), asst_obj.clone()));
+        }
+
+        Ok(asst_obj)
+    }
+
+    /// To be called on any change of the assistant (instructions, model, tools, switch).
+    fn invalidate_asst_cache(&self) {
+        if let Ok(mut cache) = self.asst_cache.lock() {
+            cache.take();
+        }
+    }
+
+    /// The model of the runs with these `options`, the assistant one by default.
+    fn run_model(&self, options: &RunOptions) -> String {
+        options.model.clone().unwrap_or_else(|| self.asst_config().model.clone())
+    }
+
+    /// Adds the usage of a run to the session totals of its model (see `session_usage`).
+    fn add_usage(&self, model: &str, usage: &Usage) {
+        let Ok(mut usage_by_model) = self.usage_by_model.lock() else {
+            return;
+        };
+        match usage_by_model.iter_mut().find(|(m, _)| m == model) {
+            Some((_, total)) => *total += usage,
+            None => usage_by_model.push((model.to_string(), usage.clone())),
+        }
+    }
+
+    /// The run options from the config (token limits), and the `BUDDY_MODEL` env variable.
+    fn run_options(&self) -> RunOptions {
+        RunOptions {
+            model: std::env::var(ENV_BUDDY_MODEL).ok(),
+            max_prompt_tokens: self.config.max_prompt_tokens,
+            max_completion_tokens: self.config.max_completion_tokens,
+            answer_fetch_retries: self.config.answer_fetch_retries,
+            deadline: self.session_deadline,
+            tool_handlers: self.tool_handlers.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Uploads the instructions and files of the active assistant,
+    /// all the bundles with `recreate_files` (see `upload_files`).
+    pub async fn sync_asst(&self, recreate_files: bool) -> Result<UploadSummary> {
+        self.log_event(Event::AsstLoaded {
+            asst_id: self.asst_id.to_string(),
+            name: self.name().to_string(),
+        });
+
+        // Upload the instructions
+        self.upload_instructions().await?;
+
+        // Upload the file
+        let summary = self.upload_files(recreate_files).await?;
+        self.log_event(Event::FilesUploaded {
+            count: summary.uploaded,
+        });
+
+        Ok(summary)
+    }
+
+    /// The buddy data dir, `.buddy` by default.
+    ///
+    /// Can be set with the `BUDDY_DATA_DIR` env variable or the `data_dir` config
+    /// (relative paths are relative to the buddy dir).
     fn data_dir(&self) -> Result<PathBuf> {
-        let data_dir = self.dir.join(".buddy");
-        ensure_dir(&data_dir)?;
-        Ok(data_dir)
+        config_data_dir(&self.dir, &self.config)
+    }
+
+    /// The `.buddy/images` dir, where the message images are downloaded
+    /// (created on first download).
+    fn images_dir(&self) -> Result<PathBuf> {
+        Ok(self.data_dir()?.join("images"))
+    }
+
+    /// The data dir of the active assistant (conversations and files).
+    /// The main assistant uses the data dir, the others `<data_dir>/assistants/<name>`.
+    fn asst_data_dir(&self) -> Result<PathBuf> {
+        if self.current == 0 {
+            return self.data_dir();
+        }
+        let dir = self.data_dir()?.join("assistants").join(self.name());
+        ensure_dir(&dir)?;
+        Ok(dir)
+    }
+
+    /// The history cache file of the conversation, `None` for a scratch conversation.
+    fn history_file(&self, conv: &Conv) -> Result<Option<PathBuf>> {
+        if conv.file.is_none() {
+            return Ok(None);
+        }
+        let dir = self.data_dir()?.join("convs");
+        ensure_dir(&dir)?;
+        Ok(Some(dir.join(format!("{}.history.jsonl", conv.thread_id))))
     }
 
     fn data_files_dir(&self) -> Result<PathBuf> {
-        let dir = self.data_dir()?.join("files");
+        let dir = self.asst_data_dir()?.join("files");
         ensure_dir(&dir)?;
         Ok(dir)
     }
+
+    /// Applies the `context_guardrail` when the estimated tokens of the conversation
+    /// exceed `context_max_tokens`.
+    ///
+    /// With `summarize`, `conv` becomes a new thread seeded with the summary of the older messages
+    /// and the recent ones (see `split_for_summary`), persisted in place
+    /// of the previous one (kept, except for a scratch conversation which is deleted).
+    async fn guard_context(&self, conv: &mut Conv) -> Result<()> {
+        let guardrail = self.config.context_guardrail.unwrap_or_default();
+        if guardrail == ContextGuardrail::Off {
+            return Ok(());
+        }
+
+        let max_tokens = self.config.context_max_tokens.unwrap_or(DEFAULT_CONTEXT_MAX_TOKENS) as usize;
+        let msgs = self.history(conv).await?;
+        let tokens: usize = msgs.iter().map(|msg| estimate_tokens(&msg.content)).sum();
+        if tokens <= max_tokens {
+            return Ok(());
+        }
+
+        if guardrail == ContextGuardrail::Warn {
+            println!(
+                "{} Conversation is ~{tokens} tokens (over {max_tokens}), runs might fail. Consider /summary or a new one.",
+                ico_err()
+            );
+            return Ok(());
+        }
+
+        // NOTE: Only the older messages fitting under the limit are summarized (not the whole
+        //       oversized context), and the recent ones are carried over verbatim after the summary.
+        let (older, recent) = split_for_summary(msgs, max_tokens);
+        let summary = self.summarize_msgs(older).await?;
+        let summary_msg = ChatMsg {
+            role: "user".to_string(),
+            content: format!("[summary of the previous conversation]\n{summary}"),
+        };
+        let thread_id = self.seed_thread_msgs(std::iter::once(summary_msg).chain(recent).collect()).await?;
+
+        let continuation = Conv::new(thread_id, conv.file.clone());
+        if let Some(file) = &continuation.file {
+            save_to_json(file, &continuation)?;
+        } else {
+            let _ = asst::delete_thread(&self.oac, &conv.thread_id).await;
+        }
+        *conv = continuation;
+
+        if show_status() {
+            println!(
+                "{} Conversation over ~{max_tokens} tokens, continued in a new summarized one",
+                ico_check()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Creates a new thread with the first `upto` messages of the conversation
+    /// (assistant answers replayed as user messages, see `fork_conv`).
+    async fn seed_thread(&self, conv: &Conv, upto: usize) -> Result<ThreadId> {
+        let msgs = self.history(conv).await?;
+        self.seed_thread_msgs(msgs.into_iter().take(upto).collect()).await
+    }
+
+    /// Creates a new thread with the messages (assistant answers replayed as user messages).
+    /// The thread is deleted when a message fails to be added.
+    async fn seed_thread_msgs(&self, msgs: Vec<ChatMsg>) -> Result<ThreadId> {
+        let thread_id = asst::create_thread(&self.oac).await?;
+        for msg in msgs {
+            if msg.content.is_empty() {
+                continue;
+            }
+            let text = match msg.role.as_str() {
+                "assistant" => format!("[assistant answer]\n{}", msg.content),
+                _ => msg.content,
+            };
+            if let Err(err) = asst::add_thread_msg(&self.oac, &thread_id, &text).await {
+                // Best-effort cleanup of the partial thread.
+                let _ = asst::delete_thread(&self.oac, &thread_id).await;
+                return Err(err);
+            }
+        }
+
+        Ok(thread_id)
+    }
+
+    /// Bundles all the `bundles` first, then uploads them concurrently (`upload_concurrency`).
+    async fn upload_bundles(&self, bundles: &[&FileBundle], recreate: bool) -> Result<UploadSummary> {
+        let mut summary = UploadSummary::default();
+
+        let hashes_file = self.data_dir()?.join(BUNDLE_HASHES_JSON);
+        let mut hashes: HashMap<String, String> = load_from_json(&hashes_file).unwrap_or_default();
+
+        // -- Bundle
+        let mut prepared = Vec::new();
+        for (idx, bundle) in bundles.iter().enumerate() {
+            match self.prepare_bundle(bundle, &hashes, recreate) {
+                Ok(Some(prep)) => prepared.push((idx, prep)),
+                Ok(None) => (),
+                Err(err) => {
+                    print_bundle_failed(&bundle.bundle_name, &err);
+                    summary.failed.push(bundle.bundle_name.clone());
+                }
+            }
+        }
+
+        // -- Upload
+        let concurrency = self.config.upload_concurrency.unwrap_or(DEFAULT_UPLOAD_CONCURRENCY) as usize;
+        let mut results: Vec<(usize, PreparedBundle, Result<bool>)> = stream::iter(prepared)
+            .map(|(idx, prep)| async move {
+                let res = asst::upload_file_by_name(&self.oac, &self.asst_id, &prep.file, prep.force)
+                    .await
+                    .map(|(_, uploaded)| uploaded);
+                (idx, prep, res)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+        results.sort_by_key(|(idx, ..)| *idx);
+
+        let mut hashes_changed = false;
+        for (idx, prep, res) in results {
+            let bundle = bundles[idx];
+            match res {
+                Ok(uploaded) => {
+                    if uploaded {
+                        summary.uploaded += 1;
+                    }
+                    let file_name = prep.file.x_file_name().to_string();
+                    if hashes.get(&file_name) != Some(&prep.hash) {
+                        hashes.insert(file_name, prep.hash);
+                        hashes_changed = true;
+                    }
+                }
+                Err(err) => {
+                    print_bundle_failed(&bundle.bundle_name, &err);
+                    summary.failed.push(bundle.bundle_name.clone());
+                }
+            }
+        }
+        if hashes_changed {
+            save_to_json(&hashes_file, &hashes)?;
+        }
+
+        if !summary.failed.is_empty() {
+            println!(
+                "{} {} of {} bundles failed (retry with /retry-failed-uploads)",
+                ico_err(),
+                summary.failed.len(),
+                bundles.len()
+            );
+        }
+        if let Ok(mut failed) = self.failed_bundles.lock() {
+            failed.clone_from(&summary.failed);
+        }
+
+        Ok(summary)
+    }
+
+    /// Warns about the bundles matching no source file (nothing uploaded for them),
+    /// most likely a misconfigured `src_dir` or `src_globs`.
+    ///
+    /// NOTE: No `file_bundles` at all is intentional, so no warning.
+    fn warn_empty_bundles(&self, bundles: &[&FileBundle]) {
+        let mut empty = Vec::new();
+        for bundle in bundles {
+            let src_dir = self.dir.join(&bundle.src_dir);
+            if !src_dir.is_dir() {
+                empty.push(format!(
+                    "'{}': src_dir '{}' not found",
+                    bundle.bundle_name,
+                    src_dir.display()
+                ));
+            } else if matches!(self.bundle_src_files(bundle), Ok(files) if files.is_empty()) {
+                empty.push(format!(
+                    "'{}': no file in '{}' matches {:?}",
+                    bundle.bundle_name,
+                    src_dir.display(),
+                    bundle.src_globs
+                ));
+            }
+        }
+
+        if !empty.is_empty() {
+            println!(
+                "{} {} of {} bundles have no source files, the assistant will not see them:",
+                ico_err(),
+                empty.len(),
+                bundles.len()
+            );
+            for line in empty {
+                println!("  - {line}");
+            }
+        }
+    }
+
+    /// Bundles one bundle into the data files dir (see the `prepare_bundle` fn).
+    fn prepare_bundle(
+        &self,
+        bundle: &FileBundle,
+        hashes: &HashMap<String, String>,
+        recreate: bool,
+    ) -> Result<Option<PreparedBundle>> {
+        let bundle_file = self.data_files_dir()?.join(self.bundle_file_name(bundle));
+        prepare_bundle(&self.dir, bundle, bundle_file, hashes, recreate)
+    }
+
+    fn bundle_src_files(&self, bundle: &FileBundle) -> Result<Vec<PathBuf>> {
+        bundle_src_files(&self.dir, bundle)
+    }
+
+    fn bundle_file_name(&self, bundle: &FileBundle) -> String {
+        bundle_file_name(&self.config, self.name(), &self.asst_id, bundle)
+    }
+
+    /// Returns the file ids of the active bundles to attach to a message,
+    /// or `None` when all bundles are active (assistant files only).
+    async fn active_file_ids(&self) -> Result<Option<Vec<FileId>>> {
+        if self.active_bundles.is_empty() {
+            return Ok(None);
+        }
+
+        let mut file_id_by_name = asst::get_file_hashmap(&self.oac, &self.asst_id).await?;
+        let file_ids = self
+            .asst_config()
+            .file_bundles
+            .iter()
+            .filter(|b| self.active_bundles.contains(&b.bundle_name))
+            .filter_map(|b| file_id_by_name.remove(&self.bundle_file_name(b)))
+            .collect();
+
+        Ok(Some(file_ids))
+    }
+
+    /// Best-effort append to the `.buddy/events.jsonl` log (none in read-only mode).
+    fn log_event(&self, event: Event) {
+        if is_read_only() {
+            return;
+        }
+        if let Ok(data_dir) = self.data_dir() {
+            append_event(&data_dir.join(EVENTS_JSONL), &event);
+        }
+    }
+}
+
+/// Returns the `dir` when it has a buddy config, otherwise the closest one walking up
+/// from the current dir (like git), either `<ancestor>/<dir>` or the ancestor itself.
+fn find_buddy_dir(dir: &Path) -> Result<PathBuf> {
+    if has_config(dir) {
+        return Ok(dir.to_path_buf());
+    }
+
+    let cwd = std::env::current_dir()?;
+    for ancestor in cwd.ancestors() {
+        let candidates = [ancestor.join(dir), ancestor.to_path_buf()];
+        if let Some(found) = candidates.into_iter().find(|c| has_config(c)) {
+            return Ok(found);
+        }
+    }
+
+    Err(format!(
+        "No {BUDDY_TOML} found in '{}' nor in the parent dirs of '{}'",
+        dir.display(),
+        cwd.display()
+    )
+    .into())
+}
+
+/// Loads the `.env` of the buddy dir, then the one of the current dir.
+///
+/// NOTE: Already set env variables are not overridden, so the process env wins,
+///       then the buddy `.env`, then the current dir one.
+fn load_dot_envs(dir: &Path) {
+    let mut files = vec![dir.join(DOT_ENV)];
+    if let Ok(cwd) = std::env::current_dir() {
+        files.push(cwd.join(DOT_ENV));
+    }
+    for file in files.iter().filter(|f| f.is_file()) {
+        match dotenv::from_path(file) {
+            Ok(()) if show_details() => println!("{} Env loaded from '{}'", ico_check(), file.display()),
+            Ok(()) => (),
+            Err(err) => println!("{} Can't load '{}': {err}", ico_err(), file.display()),
+        }
+    }
 }
+
+/// Bundles the files of the buddy (main assistant) and prints what would be uploaded
+/// (file count, bytes), without the API (no key, no assistant needed).
+///
+/// The bundles are generated in `<data_dir>/dry-run`, the `{asst_id}` of their name being `dry-run`.
+/// The failed bundles are in the summary, nothing being uploaded.
+pub fn dry_run(dir: &Path, config_file: Option<&Path>) -> Result<UploadSummary> {
+    let (dir, config) = match config_file {
+        Some(config_file) => {
+            let dir = match config_file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            (dir, load_config_file(config_file)?)
+        }
+        None => {
+            let dir = find_buddy_dir(dir)?;
+            let config = load_config(&dir)?;
+            (dir, config)
+        }
+    };
+    config.validate()?;
+    set_theme(config.theme()?);
+    load_dot_envs(&dir);
+
+    let out_dir = config_data_dir(&dir, &config)?.join(DRY_RUN_DIR);
+    ensure_dir(&out_dir)?;
+    let asst_config = &config.asst_configs()[0];
+
+    let mut summary = UploadSummary::default();
+    println!("{} Dry run, nothing uploaded:", ico_check());
+    for bundle in asst_config.file_bundles.iter() {
+        let file_count = bundle_src_files(&dir, bundle)?.len();
+        let bundle_file = out_dir.join(bundle_file_name(&config, &asst_config.name, DRY_RUN_DIR, bundle));
+        match prepare_bundle(&dir, bundle, bundle_file, &HashMap::new(), true) {
+            Ok(Some(prep)) => {
+                let bytes = fs::metadata(&prep.file).map(|m| m.len()).unwrap_or(0);
+                println!(
+                    "  {} -> {file_count} files, {bytes} bytes ({})",
+                    bundle.bundle_name,
+                    prep.file.display()
+                );
+            }
+            Ok(None) => println!("  {} -> no source files", bundle.bundle_name),
+            Err(err) => {
+                print_bundle_failed(&bundle.bundle_name, &err);
+                summary.failed.push(bundle.bundle_name.clone());
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Restores a snapshot of `export_snapshot` into the dir, which must not have a buddy config yet.
+///
+/// NOTE: Only the local `.buddy` layout is restored. At the first launch, the assistant and its files
+///       are recreated in the account, and the conversations only resume when their threads
+///       still exist in it (otherwise, see the `transcript.jsonl`).
+pub fn import_snapshot(archive_file: &Path, dir: &Path) -> Result<()> {
+    if has_config(dir) {
+        return Err(format!("'{}' already has a buddy config, import into a new dir", dir.display()).into());
+    }
+    ensure_dir(dir)?;
+
+    // NOTE: `unpack` skips the entries escaping the dir (e.g., `..`).
+    let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(archive_file)?));
+    archive
+        .unpack(dir)
+        .map_err(|err| format!("Can't import snapshot '{}'\n  cause: {err}", archive_file.display()))?;
+
+    Ok(())
+}
+
+/// Writes the snapshot archive (see `Buddy::export_snapshot`), the data dir as `data_dir_name`.
+///
+/// NOTE: The transcript of an imported snapshot is replaced by the fresh one (no duplicate entry),
+///       and the archive itself is skipped when written in the data dir.
+fn write_snapshot(file: &Path, config_toml: &str, data_dir: &Path, data_dir_name: &Path, transcript: &str) -> Result<()> {
+    let mut archive = tar::Builder::new(GzEncoder::new(fs::File::create(file)?, Compression::default()));
+    append_snapshot_data(&mut archive, Path::new(BUDDY_TOML), config_toml.as_bytes())?;
+    append_snapshot_data(&mut archive, &data_dir_name.join(SNAPSHOT_TRANSCRIPT), transcript.as_bytes())?;
+
+    let skipped = [data_dir.join(SNAPSHOT_TRANSCRIPT).canonicalize().ok(), file.canonicalize().ok()];
+    for entry in WalkDir::new(data_dir).min_depth(1).sort_by_file_name() {
+        let entry = entry.map_err(std::io::Error::from)?;
+        let path = entry.path();
+        if skipped.iter().flatten().any(|skipped| path.canonicalize().ok().as_ref() == Some(skipped)) {
+            continue;
+        }
+        let name = data_dir_name.join(path.strip_prefix(data_dir).unwrap_or(path));
+        if entry.file_type().is_dir() {
+            archive.append_dir(&name, path)?;
+        } else {
+            archive.append_path_with_name(path, &name)?;
+        }
+    }
+    archive.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// Appends the in-memory content as the `path` file of the snapshot archive.
+fn append_snapshot_data(archive: &mut tar::Builder<impl std::io::Write>, path: &Path, data: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, path, data)?;
+
+    Ok(())
+}
+
+/// The buddy data dir, `.buddy` by default (see `Buddy::data_dir`).
+fn config_data_dir(dir: &Path, config: &Config) -> Result<PathBuf> {
+    let data_dir = std::env::var(ENV_BUDDY_DATA_DIR)
+        .ok()
+        .or_else(|| config.data_dir.clone())
+        .unwrap_or_else(|| DEFAULT_DATA_DIR.to_string());
+    let data_dir = dir.join(data_dir);
+    ensure_dir(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Bundles one bundle into `bundle_file` when its sources changed (or `recreate`),
+/// `None` when it has no source files.
+///
+/// NOTE: Unchanged sources (same hash as in `hashes`) are not rebundled,
+///       and only uploaded when the bundle file is not attached to the assistant.
+fn prepare_bundle(
+    dir: &Path,
+    bundle: &FileBundle,
+    bundle_file: PathBuf,
+    hashes: &HashMap<String, String>,
+    recreate: bool,
+) -> Result<Option<PreparedBundle>> {
+    let files = bundle_src_files(dir, bundle)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+
+    // The hash of the sources (and of the bundle config, for the transforms and limits).
+    let hash = hash_files_meta(&files, &serde_json::to_string(bundle)?)?;
+    let unchanged = bundle_file.exists() && hashes.get(bundle_file.x_file_name()) == Some(&hash);
+
+    let force = recreate || !unchanged;
+    if force {
+        bundle_to_file(files, &bundle_file, &bundle.try_into()?)?;
+    }
+
+    Ok(Some(PreparedBundle {
+        file: bundle_file,
+        hash,
+        force,
+    }))
+}
+
+/// The source files matched by the bundle (none when its src dir does not exist).
+fn bundle_src_files(dir: &Path, bundle: &FileBundle) -> Result<Vec<PathBuf>> {
+    let src_dir = dir.join(&bundle.src_dir);
+    if !src_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
+    let exclude_globs: Vec<&str> = bundle.exclude_globs.iter().map(AsRef::as_ref).collect();
+    let ignore_globs = load_ignore_globs(&dir.join(BUDDY_IGNORE))?;
+
+    list_files(&src_dir, Some(&src_globs), Some(&exclude_globs), ignore_globs.as_ref())
+}
+
+/// The bundle file name from the `bundle_file_name` template (see `DEFAULT_BUNDLE_FILE_NAME`).
+fn bundle_file_name(config: &Config, name: &str, asst_id: &str, bundle: &FileBundle) -> String {
+    config
+        .bundle_file_name
+        .as_deref()
+        .unwrap_or(DEFAULT_BUNDLE_FILE_NAME)
+        .replace("{name}", name)
+        .replace("{bundle}", &bundle.bundle_name)
+        .replace("{asst_id}", asst_id)
+        .replace("{ext}", &bundle.dst_ext)
+}
+
+/// Splits the messages of an over-limit conversation (oldest first) into the older ones to summarize
+/// and the recent ones to carry over verbatim (up to a `CONTEXT_RECENT_SHARE` of `max_tokens`).
+///
+/// NOTE: Only the newest of the older messages fitting in the rest of `max_tokens` are summarized,
+///       so the summary run stays under the limit too.
+fn split_for_summary(mut msgs: Vec<ChatMsg>, max_tokens: usize) -> (Vec<ChatMsg>, Vec<ChatMsg>) {
+    let recent_max = max_tokens / CONTEXT_RECENT_SHARE;
+    let older_max = max_tokens - recent_max;
+
+    // Index of the first message of the newest ones fitting in `max`, before `end`.
+    let fitting_start = |msgs: &[ChatMsg], end: usize, max: usize| {
+        let mut tokens = 0;
+        let mut start = end;
+        while start > 0 {
+            tokens += estimate_tokens(&msgs[start - 1].content);
+            if tokens > max {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    };
+
+    let recent_start = fitting_start(&msgs, msgs.len(), recent_max);
+    let older_start = fitting_start(&msgs, recent_start, older_max);
+    let recent = msgs.split_off(recent_start);
+    let older = msgs.split_off(older_start);
+
+    (older, recent)
+}
+
+/// Prints the failed upload of a bundle, the other bundles going on.
+fn print_bundle_failed(bundle_name: &str, err: &Error) {
+    println!("{} Bundle '{bundle_name}' failed to upload\n  cause: {err}", ico_err());
+}
+
+/// Returns true when the dir has a buddy config (toml or yaml).
+pub fn has_config(dir: &Path) -> bool {
+    std::iter::once(&BUDDY_TOML)
+        .chain(BUDDY_YAML_FILES)
+        .any(|name| dir.join(name).is_file())
+}
+
+/// Loads a toml, or yaml (`.yaml`/`.yml`), config file.
+fn load_config_file(file: &Path) -> Result<Config> {
+    if !file.is_file() {
+        return Err(format!("Config file '{}' not found", file.display()).into());
+    }
+
+    match file.x_extension() {
+        "yaml" | "yml" => load_from_yaml(file),
+        _ => load_from_toml(file),
+    }
+}
+
+/// Loads the `buddy.toml` of the dir, or its `buddy.yaml`/`buddy.yml` alternative.
+/// Errors if none or more than one exist.
+fn load_config(dir: &Path) -> Result<Config> {
+    let config_files: Vec<PathBuf> = std::iter::once(&BUDDY_TOML)
+        .chain(BUDDY_YAML_FILES)
+        .map(|name| dir.join(name))
+        .filter(|file| file.is_file())
+        .collect();
+
+    match config_files.as_slice() {
+        [] => Err(format!("No {BUDDY_TOML} found in '{}'", dir.display()).into()),
+        [file] => load_config_file(file),
+        files => Err(format!(
+            "Multiple buddy config files found, keep only one: {}",
+            files
+                .iter()
+                .map(|f| f.x_file_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+// region: --- Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn write_snapshot_fresh_transcript_once() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let data_dir = tmp.path().join(DEFAULT_DATA_DIR);
+        ensure_dir(&data_dir.join("convs"))?;
+        // -- As after an import: a stale transcript in the data dir.
+        fs::write(data_dir.join(SNAPSHOT_TRANSCRIPT), "stale\n")?;
+        fs::write(data_dir.join("convs").join("conv.json"), "{}")?;
+        let archive_file = data_dir.join("snapshot.tar.gz");
+
+        write_snapshot(&archive_file, "name = \"b\"\n", &data_dir, Path::new(DEFAULT_DATA_DIR), "fresh\n")?;
+
+        let mut entries: Vec<(String, String)> = Vec::new();
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(&archive_file)?));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.to_string_lossy().to_string();
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            entries.push((path, content));
+        }
+
+        let transcripts: Vec<&str> = entries
+            .iter()
+            .filter(|(path, _)| path.ends_with(SNAPSHOT_TRANSCRIPT))
+            .map(|(_, content)| content.as_str())
+            .collect();
+        assert_eq!(transcripts, ["fresh\n"]);
+        assert!(entries.iter().any(|(path, _)| path == ".buddy/convs/conv.json"));
+        assert!(!entries.iter().any(|(path, _)| path.ends_with("snapshot.tar.gz")));
+        Ok(())
+    }
+
+    fn msg(content: &str) -> ChatMsg {
+        ChatMsg {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn split_for_summary_fits_under_max() {
+        // -- 10 tokens each (40 chars), 120 tokens for a 60 max.
+        let msgs: Vec<ChatMsg> = (0..12).map(|i| msg(&format!("{i:0>40}"))).collect();
+
+        let (older, recent) = split_for_summary(msgs, 60);
+
+        // -- Recent: 15 tokens max (a quarter), older: the newest 45 tokens max before them.
+        let ids = |msgs: &[ChatMsg]| -> Vec<usize> { msgs.iter().map(|m| m.content.parse().unwrap()).collect() };
+        assert_eq!(ids(&recent), [11]);
+        assert_eq!(ids(&older), [7, 8, 9, 10]);
+    }
+}
+
+// endregion: --- Tests
@@ -1,3 +1,63 @@
+use derive_more::{Display, From};
+
 pub type Result<T> = core::result::Result<T, Error>;
 
-pub type Error = Box<dyn std::error::Error>;
\ No newline at end of file
+#[derive(Debug, Display, From)]
+pub enum Error {
+	// -- App
+	#[from(skip)]
+	#[display("No OPENAI_API_KEY env variable (nor profile, nor api_key_file), please set it")]
+	MissingApiKey,
+	#[from(skip)]
+	#[display("Can't find the thread '{_0}' of the conversation")]
+	ThreadNotFound(String),
+	#[from(skip)]
+	#[display("Run failed: {_0}")]
+	RunFailed(String),
+	/// The run was cancelled on Ctrl-C, back to the prompt.
+	#[from(skip)]
+	#[display("Run cancelled")]
+	RunCancelled,
+	/// The API host can not be reached (DNS, connection, timeout), told apart as transient.
+	#[from(skip)]
+	#[display("{_0}")]
+	Connect(String),
+	#[display("{_0}")]
+	Custom(String),
+
+	// -- Externals
+	#[display("{_0}")]
+	Io(std::io::Error),
+	#[display("{_0}")]
+	Toml(toml::de::Error),
+	#[display("{_0}")]
+	TomlSer(toml::ser::Error),
+	#[display("{_0}")]
+	Yaml(serde_yaml::Error),
+	#[display("{_0}")]
+	Json(serde_json::Error),
+	#[display("{_0}")]
+	OpenAI(async_openai::error::OpenAIError),
+	#[display("{_0}")]
+	Reqwest(reqwest::Error),
+	#[display("{_0}")]
+	Glob(globset::Error),
+	#[display("{_0}")]
+	Notify(notify::Error),
+	#[display("{_0}")]
+	Dialoguer(dialoguer::Error),
+	#[display("{_0}")]
+	Header(reqwest::header::InvalidHeaderValue),
+	#[display("{_0}")]
+	Join(tokio::task::JoinError),
+	#[display("{_0}")]
+	Utf8(std::string::FromUtf8Error),
+}
+
+impl From<&str> for Error {
+	fn from(msg: &str) -> Self {
+		Self::Custom(msg.to_string())
+	}
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,62 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{ais::asst::Usage, utils::files::append_to_jsonl};
+
+// region: --- Types
+
+/// Significant session actions, appended to `.buddy/events.jsonl`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    AsstLoaded {
+        asst_id: String,
+        name: String,
+    },
+    FilesUploaded {
+        count: u32,
+    },
+    MsgSent {
+        thread_id: String,
+        chars: usize,
+    },
+    RunCompleted {
+        thread_id: String,
+        run_id: String,
+        usage: Option<Usage>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct EventRecord<'a> {
+    ts: u64,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+// endregion: --- Types
+
+// region: --- Append
+
+/// Appends the event as one json line to the `events_file`.
+///
+/// Best-effort: a write failure is reported on stderr, but never returned.
+pub fn append_event(events_file: &Path, event: &Event) {
+    let ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    if let Err(err) = append_to_jsonl(events_file, &EventRecord { ts, event }) {
+        eprintln!("Can't write event to '{}': {}", events_file.display(), err);
+    }
+}
+
+// endregion: --- Append
@@ -0,0 +1,12 @@
+// region: --- Modules
+
+pub mod ais;
+pub mod buddy;
+mod error;
+pub mod events;
+pub mod utils;
+pub mod workspace;
+
+pub use self::error::{Error, Result};
+
+// endregion: --- Modules
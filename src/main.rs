@@ -1,44 +1,277 @@
 // region: --- Modules
 
-mod ais;
-// mod buddy;
-mod buddy;
-mod error;
-mod utils;
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use clap::{ArgAction, Parser, Subcommand};
+use console::{measure_text_width, Term};
+use futures::StreamExt;
+use notify::RecommendedWatcher;
+use tokio::sync::mpsc::UnboundedReceiver;
 
-// use ais::new_oa_client;
-use textwrap::wrap;
-
-use crate::{ buddy::Buddy, utils::cli::{prompt, ico_res, text_res, ico_err}};
-
-pub use self::error::{Error, Result};
+use rust_ai::{
+    ais::{
+        asst::{self, AsstId, RunDelta},
+        interrupt_run, msg::{AssistantMessage, Citation, RetrievalInfo}, new_oa_client, set_read_only, ActiveRun,
+        ClientOptions,
+    },
+    buddy::{dry_run, import_snapshot, Buddy, BundleStatus, Conv, DEFAULT_CONV_NAME},
+    utils::cli::{
+        ico_check, ico_deleted_ok, ico_err, ico_res, init_colors, is_raw, page, render_markdown, prompt, select, set_no_wrap, set_raw, set_verbosity, show_answers,
+        show_status, show_details, show_timings, show_trace, text_dim, text_res, text_title, wrap_text, format_utc, Verbosity,
+    },
+    utils::files::{read_to_string, XFile},
+    utils::template::{parse_template_vars, render_template_file},
+    utils::prompt_source::{AnyPrompt, LinesPrompt, OncePrompt, PromptSource, TtyPrompt},
+    workspace::Workspace,
+    Error, Result,
+};
 
 // endregion: --- Modules
 
 #[tokio::main]
 async fn main() {
-    println!();
+    let code = match start().await {
+        Ok(_) => {
+            if show_status() {
+                println!("\nBye!\n")
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("\nError: {}\n", e);
+            1
+        }
+    };
 
-    match start().await {
-        Ok(_) => println!("\nBye!\n"),
-        Err(e) => println!("\nError: {}\n", e),
-    }
+    // NOTE: Exit right away, a prompt still waiting on stdin (session timeout)
+    //       would otherwise hold the runtime shutdown.
+    std::process::exit(code);
 }
 
 const DEFAULT_DIR: &str = "buddy";
+/// Files longer than this are proposed to be sent partially by `/explain` and `/start`.
+const EXPLAIN_MAX_LINES: usize = 400;
+const EXPLAIN_PROMPT: &str = "Walk me through this file: its purpose, its main parts, and how they fit together.";
+const START_PROMPT: &str =
+    "This conversation is about the file below. Keep it in mind for my next questions, and start with a short overview of it.";
+/// Exchanges printed by `--resume`, and max chars of each message.
+const RESUME_EXCHANGES: usize = 3;
+const RESUME_MSG_CHARS: usize = 500;
+/// Max chars of the instructions shown by `/context`.
+const CONTEXT_INSTRUCTIONS_CHARS: usize = 500;
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
 
 // region: --- Types
 
+/// What ended the wait for the next input (see `next_input`).
+enum NextInput {
+    Input(String),
+    /// No more inputs from the source.
+    Ended,
+    /// The session time budget was reached before the input.
+    Expired,
+}
+
+/// Command line arguments
+#[derive(Debug, Parser)]
+#[command(version, about = "Chat with an OpenAI assistant over the files of your project.")]
+struct Args {
+    /// Buddy dir, with its `buddy.toml` (or in one of its parent dirs).
+    #[arg(long, default_value = DEFAULT_DIR)]
+    dir: PathBuf,
+    /// Recreates the assistant (and uploads its instructions and files again).
+    #[arg(long)]
+    recreate_asst: bool,
+    /// Starts the conversation over, in a new thread.
+    #[arg(long)]
+    recreate_conv: bool,
+    /// No decorative status lines, only answers and errors.
+    #[arg(short, long)]
+    quiet: bool,
+    /// Only errors.
+    #[arg(long)]
+    silent: bool,
+    /// Levels of details in addition to the normal output (`-v`, `-vv`, `-vvv`, see `Verbosity`).
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+    /// Auto refresh the instructions and files on source changes.
+    #[arg(long)]
+    watch: bool,
+    /// Throwaway conversation, never persisted, and its thread deleted at quit.
+    #[arg(long)]
+    scratch: bool,
+    /// Like `scratch`, but keeps the thread at quit.
+    #[arg(long)]
+    keep_scratch: bool,
+    /// Answers printed as is, not wrapped (e.g., piped, or soft-wrap terminal).
+    #[arg(long)]
+    no_wrap: bool,
+    /// Answers printed as the raw markdown, not rendered.
+    #[arg(long)]
+    raw: bool,
+    /// No colors, and ASCII icons (like the `NO_COLOR` env variable, and when not a terminal).
+    #[arg(long)]
+    no_color: bool,
+    /// Prints the last exchanges of the conversation at launch.
+    #[arg(long)]
+    resume: bool,
+    /// Time budget of the session, then the app exits (e.g., `30m`).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    session_timeout: Option<Duration>,
+    /// OpenAI account from the user profiles file (overrides the config one).
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Prints the bundle statuses of all the buddies under this root dir, then exits.
+    #[arg(long, value_name = "ROOT_DIR")]
+    status_all: Option<PathBuf>,
+    /// Refreshes all the buddies under this root dir, then exits.
+    #[arg(long, value_name = "ROOT_DIR")]
+    refresh_all: Option<PathBuf>,
+    /// Inputs read from this file (`-` for stdin), one per line, instead of the prompt.
+    #[arg(long, value_name = "FILE")]
+    input: Option<PathBuf>,
+    /// One-shot question, answered then the app exits (like a piped stdin without `--input`).
+    #[arg(long, value_name = "QUESTION")]
+    prompt: Option<String>,
+    /// Prints the buddy of this dir and its live assistant without changing anything, then exits.
+    #[arg(long, value_name = "BUDDY_DIR")]
+    inspect: Option<PathBuf>,
+    /// Restores this snapshot (see `/snapshot`) into the buddy dir, then exits.
+    #[arg(long, value_name = "SNAPSHOT_FILE")]
+    import: Option<PathBuf>,
+    /// Bundles the files and prints what would be uploaded (file count, bytes), without the API, then exits.
+    /// Fails when a bundle fails.
+    #[arg(long)]
+    dry_run: bool,
+    /// Config file to use instead of the `buddy.toml` of the buddy dir.
+    #[arg(long, value_name = "FILE", conflicts_with = "dir")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands run instead of the session.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Manages the assistants of the OpenAI account, whatever the buddy.
+    Assistants {
+        #[command(subcommand)]
+        action: AsstAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum AsstAction {
+    /// Lists the assistants (id and name).
+    List,
+    /// Deletes the (first) assistant with this name, and its files.
+    Delete { name: String },
+}
+
+impl Args {
+    /// Whether a single question is answered (`--prompt`, or a piped stdin without `--input`),
+    /// quiet by default so only the answer is printed.
+    ///
+    /// NOTE: Not for the modes exiting before the session (e.g., `--status-all`).
+    fn one_shot(&self) -> bool {
+        let other_mode = self.command.is_some()
+            || self.status_all.is_some()
+            || self.refresh_all.is_some()
+            || self.inspect.is_some()
+            || self.import.is_some();
+        !other_mode && (self.prompt.is_some() || (self.input.is_none() && !std::io::stdin().is_terminal()))
+    }
+
+    fn verbosity(&self) -> Verbosity {
+        if self.silent {
+            Verbosity::Silent
+        } else if self.quiet || (self.verbose == 0 && self.one_shot()) {
+            Verbosity::Quiet
+        } else {
+            match self.verbose {
+                0 => Verbosity::Normal,
+                1 => Verbosity::Verbose,
+                2 => Verbosity::Timings,
+                _ => Verbosity::Trace,
+            }
+        }
+    }
+}
+
+/// Parses a duration like `90s`, `30m`, or `2h` (seconds without unit).
+fn parse_duration(value: &str) -> Result<Duration> {
+    let (num, unit_secs) = match value.trim() {
+        v if v.ends_with('h') => (&v[..v.len() - 1], 3600),
+        v if v.ends_with('m') => (&v[..v.len() - 1], 60),
+        v if v.ends_with('s') => (&v[..v.len() - 1], 1),
+        v => (v, 1),
+    };
+    let num: u64 = num
+        .parse()
+        .map_err(|_| format!("Invalid duration '{value}' (e.g., 90s, 30m, 2h)"))?;
+
+    Ok(Duration::from_secs(num * unit_secs))
+}
+
 /// Input Command from user
 
 #[derive(Debug)]
 enum Cmd {
     Quit,
     Chat(String),
+    /// Chat with another model than the assistant one, for this message only.
+    ChatWithModel { model: String, msg: String },
     RefreshAll,
     RefreshConv,
     RefreshInst,
-    RefreshFiles
+    RefreshFiles,
+    ExportConfig,
+    /// Bundle names to attach to the next messages (empty for all).
+    Use(Vec<String>),
+    /// Fork the conversation at `upto` messages into the `name` conversation.
+    Fork { upto: usize, name: String },
+    Summary,
+    RetryFailedUploads,
+    /// Re-attach the bundle files of the account detached from the assistant.
+    Reattach,
+    Status,
+    /// Send the rendered template file as a chat message.
+    RunTemplate { file: PathBuf, args: Vec<String> },
+    Info,
+    /// Print the conversation messages, after a full refetch with `sync`.
+    History { sync: bool },
+    /// Write a snapshot of the buddy (config, data dir, transcript) to the archive file.
+    Snapshot(PathBuf),
+    /// Export the conversation as chat completions json lines.
+    ExportJsonl(PathBuf),
+    /// Send the message with the image attached (vision models).
+    Image { file: PathBuf, msg: String },
+    /// Send a local file inline to be explained (not bundled).
+    Explain(PathBuf),
+    /// Start a new conversation seeded with the file content.
+    Start(PathBuf),
+    /// What the model can currently see (instructions, files, messages).
+    Context,
+    /// Additional instructions for the next message only.
+    Note(String),
+    /// Delete the assistant and its local data, then quit.
+    Destroy { keep_files: bool },
+    /// Switch to the named assistant (or list them when empty).
+    SwitchAsst(String),
+    /// Create the named conversation and switch to it.
+    NewConv(String),
+    /// Switch to the named (existing) conversation.
+    SwitchConv(String),
+    /// List the conversations of the assistant.
+    ListConvs,
+    /// Print the token usage of the session, and its estimated cost.
+    Tokens,
+    /// Toggle the auto refresh on source changes (like `--watch`).
+    Watch,
+    Invalid(String),
 }
 
 impl Cmd {
@@ -55,6 +288,86 @@ impl Cmd {
 			Self::RefreshFiles
 		} else if input == "/rc" {
 			Self::RefreshConv
+		} else if input == "/buddy" || input.starts_with("/buddy ") {
+			Self::SwitchAsst(input.trim_start_matches("/buddy").trim().to_string())
+		} else if let Some(args) = input.strip_prefix("/run ") {
+			let mut args = args.split_whitespace();
+			match args.next() {
+				Some(file) => Self::RunTemplate {
+					file: PathBuf::from(file),
+					args: args.map(String::from).collect(),
+				},
+				None => Self::Invalid("usage: /run <template_file> key=value ...".to_string()),
+			}
+		} else if let Some(note) = input.strip_prefix("/note ") {
+			Self::Note(note.trim().to_string())
+		} else if let Some(args) = input.strip_prefix("/m ") {
+			match args.trim().split_once(char::is_whitespace) {
+				Some((model, msg)) => Self::ChatWithModel {
+					model: model.to_string(),
+					msg: msg.trim().to_string(),
+				},
+				None => Self::Invalid("usage: /m <model> <message>".to_string()),
+			}
+		} else if let Some(args) = input.strip_prefix("/img ") {
+			match args.trim().split_once(char::is_whitespace) {
+				Some((file, msg)) => Self::Image {
+					file: PathBuf::from(file),
+					msg: msg.trim().to_string(),
+				},
+				None => Self::Invalid("usage: /img <image_file> <prompt>".to_string()),
+			}
+		} else if let Some(file) = input.strip_prefix("/explain ") {
+			Self::Explain(PathBuf::from(file.trim()))
+		} else if let Some(file) = input.strip_prefix("/start ") {
+			Self::Start(PathBuf::from(file.trim()))
+		} else if let Some(file) = input.strip_prefix("/snapshot ") {
+			Self::Snapshot(PathBuf::from(file.trim()))
+		} else if let Some(file) = input.strip_prefix("/export-jsonl ") {
+			Self::ExportJsonl(PathBuf::from(file.trim()))
+		} else if input == "/history" {
+			Self::History { sync: false }
+		} else if input == "/history --sync" {
+			Self::History { sync: true }
+		} else if input == "/context" {
+			Self::Context
+		} else if input == "/info" {
+			Self::Info
+		} else if input == "/destroy" {
+			Self::Destroy { keep_files: false }
+		} else if input == "/destroy --keep-files" {
+			Self::Destroy { keep_files: true }
+		} else if input == "/status" {
+			Self::Status
+		} else if input == "/retry-failed-uploads" {
+			Self::RetryFailedUploads
+		} else if input == "/reattach" {
+			Self::Reattach
+		} else if input == "/summary" {
+			Self::Summary
+		} else if input == "/export-config" {
+			Self::ExportConfig
+		} else if let Some(args) = input.strip_prefix("/fork ") {
+			let mut args = args.split_whitespace();
+			match (args.next().map(str::parse::<usize>), args.next()) {
+				(Some(Ok(upto)), Some(name)) => Self::Fork {
+					upto,
+					name: name.to_string(),
+				},
+				_ => Self::Invalid("usage: /fork <num_messages> <name>".to_string()),
+			}
+		} else if let Some(name) = input.strip_prefix("/new ") {
+			Self::NewConv(name.trim().to_string())
+		} else if let Some(name) = input.strip_prefix("/switch ") {
+			Self::SwitchConv(name.trim().to_string())
+		} else if input == "/convs" {
+			Self::ListConvs
+		} else if input == "/tokens" {
+			Self::Tokens
+		} else if input == "/watch" {
+			Self::Watch
+		} else if input == "/use" || input.starts_with("/use ") {
+			Self::Use(input.split_whitespace().skip(1).map(String::from).collect())
 		} else {
 			Self::Chat(input)
 		}
@@ -64,28 +377,903 @@ impl Cmd {
 
 
 async fn start() -> Result<()> {
-    let  buddy = Buddy::init_form_dir(DEFAULT_DIR, false).await?;
+    let session_start = Instant::now();
+    let args = Args::parse();
+    set_verbosity(args.verbosity());
+    set_no_wrap(args.no_wrap);
+    set_raw(args.raw);
+    init_colors(args.no_color);
+
+    // -- Ctrl-C cancels the active run (back to the prompt), or quits otherwise
+    // NOTE: At the prompt, Ctrl-C ends the input instead (see `TtyPrompt`).
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if !interrupt_run() {
+                if show_status() {
+                    println!("\nBye!\n");
+                }
+                std::process::exit(0);
+            }
+        }
+    });
+
+    if show_status() {
+        println!();
+    }
+
+    if let Some(file) = &args.import {
+        import_snapshot(file, &args.dir)?;
+        if show_status() {
+            println!("{} Snapshot imported into '{}'", ico_check(), args.dir.display());
+            println!("  The assistant and its files are recreated at the first launch (server-side state is not in the snapshot).");
+        }
+        return Ok(());
+    }
+    if let Some(Command::Assistants { action }) = &args.command {
+        return manage_assistants(action, args.profile.as_deref()).await;
+    }
+    if let Some(dir) = &args.inspect {
+        return inspect(dir, args.profile.as_deref()).await;
+    }
+    if let Some(root) = &args.status_all {
+        return status_all(root, args.profile.as_deref()).await;
+    }
+    if let Some(root) = &args.refresh_all {
+        return refresh_all(root, args.profile.as_deref()).await;
+    }
+
+    // NOTE: Without the API, so no key nor assistant needed.
+    if args.dry_run {
+        let summary = dry_run(&args.dir, args.config.as_deref())?;
+        if !summary.failed.is_empty() {
+            return Err(format!("{} bundles failed", summary.failed.len()).into());
+        }
+        return Ok(());
+    }
+
+    let mut buddy = match &args.config {
+        Some(config_file) => {
+            Buddy::init_from_config(config_file, args.recreate_asst, args.profile.as_deref()).await?
+        }
+        None => Buddy::init_form_dir(&args.dir, args.recreate_asst, args.profile.as_deref()).await?,
+    };
+    if show_status() {
+        let dir = buddy.dir().canonicalize().unwrap_or_else(|_| buddy.dir().to_path_buf());
+        println!("{} Buddy dir '{}'", ico_check(), dir.display());
+    }
+
+    if show_timings() {
+        println!("{}", text_dim(format!("(init {:.1}s)", session_start.elapsed().as_secs_f32())));
+    }
+
+    if let Some(timeout) = args.session_timeout {
+        buddy.set_session_deadline(session_start + timeout);
+    }
+
+    let scratch = args.scratch || args.keep_scratch;
+    let mut conv_name = DEFAULT_CONV_NAME.to_string();
+    let mut conv = load_conv(&buddy, &conv_name, scratch, args.recreate_conv).await?;
+    if !scratch && (args.resume || buddy.resume()) && show_answers() {
+        if let Err(err) = print_last_exchanges(&buddy, &conv).await {
+            println!("{} Can't get the last messages: {err}", ico_err());
+        }
+    }
 
-    let  conv = buddy.load_or_create_conv(false).await?;
+    // -- Watch the sources (the watcher must live as long as the loop)
+    let mut watch = if args.watch { Some(start_watch(&buddy)?) } else { None };
+
+    let mut source = match (&args.input, &args.prompt) {
+        (Some(file), _) => AnyPrompt::Lines(LinesPrompt::open(file).await?),
+        (None, Some(prompt)) => AnyPrompt::Once(OncePrompt::new(prompt)),
+        (None, None) if args.one_shot() => AnyPrompt::Once(OncePrompt::stdin().await?),
+        (None, None) => AnyPrompt::Tty(TtyPrompt),
+    };
 
     loop {
         println!();
-        let input = prompt("Ask away")?;
+        let next = next_input(&buddy, &mut source, watch.as_mut().map(|(_, changes)| changes)).await?;
+        let input = match next {
+            NextInput::Input(input) if !buddy.session_expired() => input,
+            NextInput::Ended => break,
+            _ => {
+                print_session_expired(session_start);
+                break;
+            }
+        };
+        // Nothing to send for a blank input, re-prompt.
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
         let cmd = Cmd::from_input(input);
 
         match cmd {
             Cmd::Quit => break,
-            Cmd::Chat(msg) => {
-                let res = buddy.chat(&conv, &msg).await?;
-                let res = wrap(&res, 80).join("\n");
-                println!("{} {}", ico_res(), text_res(res));
+            Cmd::Chat(msg) => chat_stream(&buddy, &mut conv, &msg).await?,
+            Cmd::ChatWithModel { model, msg } => chat(&buddy, &mut conv, &msg, Some(&model)).await?,
+            Cmd::Image { file, msg } => {
+                let res = buddy.chat_image(&mut conv, &file, &msg).await;
+                print_answer(&buddy, res).await?;
+            }
+            Cmd::RunTemplate { file, args } => {
+                let msg = parse_template_vars(args.iter().map(String::as_str))
+                    .and_then(|vars| render_template_file(&file, &vars));
+                match msg {
+                    Ok(msg) => chat(&buddy, &mut conv, &msg, None).await?,
+                    Err(err) => println!("{} {err}", ico_err()),
+                }
+            }
+            Cmd::ExportConfig => {
+                if show_answers() {
+                    println!("{}", buddy.config_toml()?);
+                }
+            }
+            Cmd::Use(bundle_names) => {
+                if let Err(err) = buddy.use_bundles(bundle_names) {
+                    println!("{} {err}", ico_err());
+                } else if show_status() {
+                    match buddy.active_bundles() {
+                        [] => println!("{} Using all bundles", ico_check()),
+                        names => println!("{} Using bundles: {}", ico_check(), names.join(", ")),
+                    }
+                }
+            }
+            Cmd::Fork { upto, name } => match buddy.fork_conv(&conv, upto, &name).await {
+                Ok(fork) => {
+                    conv = fork;
+                    conv_name = name.clone();
+                    if show_status() {
+                        println!("{} Conversation forked to '{name}'", ico_check());
+                    }
+                }
+                Err(err) => println!("{} Can't fork conversation: {err}", ico_err()),
+            },
+            Cmd::Summary => {
+                let summary = buddy.summarize_conv(&conv).await?;
+                let summary = wrap_text(&summary, 80);
+                if show_status() {
+                    page(&format!("{}\n{}", text_title("Conversation summary"), summary))?;
+                } else if show_answers() {
+                    page(&summary)?;
+                }
+            }
+            Cmd::SwitchAsst(name) if name.is_empty() => {
+                for asst_name in buddy.asst_names() {
+                    let marker = if asst_name == buddy.name() { "*" } else { " " };
+                    println!("{marker} {asst_name}");
+                }
+            }
+            Cmd::SwitchAsst(name) => match buddy.switch_asst(&name).await {
+                Ok(()) => {
+                    conv_name = DEFAULT_CONV_NAME.to_string();
+                    conv = load_conv(&buddy, &conv_name, scratch, false).await?;
+                }
+                Err(err) => println!("{} {err}", ico_err()),
+            },
+            Cmd::NewConv(_) | Cmd::SwitchConv(_) | Cmd::ListConvs if scratch => {
+                println!("{} No named conversations in scratch mode", ico_err());
+            }
+            Cmd::NewConv(name) => match buddy.has_conv(&name) {
+                Ok(true) => println!("{} Conversation '{name}' already exists (use /switch)", ico_err()),
+                Ok(false) => match buddy.load_or_create_conv(&name, false).await {
+                    Ok(new_conv) => {
+                        conv = new_conv;
+                        conv_name = name;
+                    }
+                    Err(err) => println!("{} Can't create the conversation: {err}", ico_err()),
+                },
+                Err(err) => println!("{} {err}", ico_err()),
+            },
+            Cmd::SwitchConv(name) => match buddy.has_conv(&name) {
+                Ok(false) => println!("{} No conversation named '{name}' (see /convs)", ico_err()),
+                Ok(true) => match buddy.load_or_create_conv(&name, false).await {
+                    Ok(new_conv) => {
+                        conv = new_conv;
+                        if show_status() {
+                            println!("{} Switched to conversation '{name}'", ico_check());
+                        }
+                        conv_name = name;
+                    }
+                    Err(err) => println!("{} Can't load the conversation: {err}", ico_err()),
+                },
+                Err(err) => println!("{} {err}", ico_err()),
+            },
+            Cmd::ListConvs => match buddy.list_convs() {
+                Ok(names) => {
+                    for name in names {
+                        let marker = if name == conv_name { "*" } else { " " };
+                        println!("{marker} {name}");
+                    }
+                }
+                Err(err) => println!("{} Can't list the conversations: {err}", ico_err()),
+            },
+            Cmd::Tokens => print_tokens(&buddy),
+            Cmd::Watch => match watch.take() {
+                // Dropping the watcher stops it.
+                Some(_) => println!("{} Stopped watching the sources", ico_check()),
+                None => match start_watch(&buddy) {
+                    Ok(started) => watch = Some(started),
+                    Err(err) => println!("{} Can't watch the sources: {err}", ico_err()),
+                },
+            },
+            Cmd::Info => print_info(&buddy).await,
+            Cmd::Explain(file) => match file_msg(&file, EXPLAIN_PROMPT) {
+                Ok(Some(msg)) => chat(&buddy, &mut conv, &msg, None).await?,
+                Ok(None) => (),
+                Err(err) => println!("{} {err}", ico_err()),
+            },
+            Cmd::Start(file) => match file_msg(&file, START_PROMPT) {
+                Ok(Some(msg)) => {
+                    conv = if scratch {
+                        buddy.create_scratch_conv().await?
+                    } else {
+                        buddy.load_or_create_conv(&conv_name, true).await?
+                    };
+                    chat(&buddy, &mut conv, &msg, None).await?;
+                }
+                Ok(None) => (),
+                Err(err) => println!("{} {err}", ico_err()),
+            },
+            Cmd::ExportJsonl(file) => match buddy.export_thread_jsonl(&conv, &file).await {
+                Ok(count) => {
+                    if show_status() {
+                        println!("{} {count} messages exported to '{}'", ico_check(), file.display());
+                    }
+                }
+                Err(err) => println!("{} Can't export the conversation: {err}", ico_err()),
+            },
+            Cmd::History { sync } => {
+                let entries = if sync {
+                    buddy.sync_history(&conv).await
+                } else {
+                    buddy.history_entries(&conv).await
+                };
+                match entries {
+                    Ok(entries) if entries.is_empty() => println!("(no messages)"),
+                    Ok(entries) => {
+                        let history: Vec<String> = entries
+                            .into_iter()
+                            .map(|entry| {
+                                let time = match entry.ts {
+                                    0 => String::new(),
+                                    ts => format!(" {}", text_dim(format_utc(ts))),
+                                };
+                                format!("{}{time}:\n{}", entry.msg.role, wrap_text(&entry.msg.content, 80))
+                            })
+                            .collect();
+                        page(&history.join("\n\n"))?;
+                    }
+                    Err(err) => println!("{} Can't get the history: {err}", ico_err()),
+                }
+            }
+            Cmd::Snapshot(file) => match buddy.export_snapshot(&conv, &file).await {
+                Ok(()) => {
+                    if show_status() {
+                        println!("{} Snapshot written to '{}'", ico_check(), file.display());
+                    }
+                }
+                Err(err) => println!("{} Can't write the snapshot: {err}", ico_err()),
+            },
+            Cmd::Context => match buddy.context(&conv).await {
+                Ok(context) => {
+                    println!("{}", text_title("Instructions"));
+                    let chars = context.instructions.chars().count();
+                    if chars > CONTEXT_INSTRUCTIONS_CHARS {
+                        let head: String = context.instructions.chars().take(CONTEXT_INSTRUCTIONS_CHARS).collect();
+                        println!("{head}\n... ({chars} chars)");
+                    } else {
+                        println!("{}", context.instructions);
+                    }
+
+                    println!("\n{}", text_title("Files"));
+                    match context.file_names {
+                        Ok(names) if names.is_empty() => println!("(none)"),
+                        Ok(names) => names.iter().for_each(|name| println!("- {name}")),
+                        Err(err) => println!("{} {err}", ico_err()),
+                    }
+
+                    println!("\n{}", text_title("Conversation"));
+                    match context.msg_count {
+                        Ok(count) => println!("{count} messages"),
+                        Err(err) => println!("{} {err}", ico_err()),
+                    }
+                }
+                Err(err) => println!("{} Can't get the context: {err}", ico_err()),
+            },
+            Cmd::Note(note) => {
+                buddy.set_note(note)?;
+                if show_status() {
+                    println!("{} Note added to the next message", ico_check());
+                }
+            }
+            Cmd::Destroy { keep_files } => {
+                let name = buddy.name().to_string();
+                let confirm = prompt(&format!("Type '{name}' to delete the assistant and its data"))?;
+                if confirm.trim() == name {
+                    buddy.destroy(keep_files).await?;
+                    return Ok(());
+                }
+                println!("{} Destroy cancelled", ico_err());
+            }
+            Cmd::Status => {
+                let infos = buddy.bundle_files()?;
+                for (bundle_name, status) in buddy.status().await? {
+                    let ico = match status {
+                        BundleStatus::UpToDate => ico_check(),
+                        _ => ico_err(),
+                    };
+                    match infos.iter().find(|info| info.name == bundle_name) {
+                        Some(info) => {
+                            println!(
+                                "{ico} {bundle_name}: {status} ({} files, {} bytes)",
+                                info.file_count, info.size
+                            );
+                            if show_status() && info.size > 0 {
+                                println!("    {}", info.path.display());
+                            }
+                        }
+                        None => println!("{ico} {bundle_name}: {status}"),
+                    }
+                }
+            }
+            Cmd::RetryFailedUploads => {
+                if buddy.failed_bundles().is_empty() {
+                    if show_status() {
+                        println!("{} No failed uploads to retry", ico_check());
+                    }
+                } else {
+                    let summary = buddy.retry_failed_uploads().await?;
+                    if summary.failed.is_empty() && show_status() {
+                        println!("{} {} bundles uploaded", ico_check(), summary.uploaded);
+                    }
+                }
+            }
+            Cmd::Reattach => match buddy.reattach_files().await {
+                Ok(names) if names.is_empty() => {
+                    if show_status() {
+                        println!("{} No detached bundle files to re-attach", ico_check());
+                    }
+                }
+                Ok(names) => {
+                    for name in names {
+                        println!("{} Re-attached '{name}'", ico_check());
+                    }
+                }
+                Err(err) => println!("{} {err}", ico_err()),
             },
-            other => println!("{} command not supported {other:?}", ico_err()),
+            Cmd::RefreshInst => refresh_inst(&buddy).await,
+            Cmd::RefreshFiles => refresh_files(&buddy).await,
+            Cmd::RefreshConv => {
+                if let Some(new_conv) = refresh_conv(&buddy, &conv_name, scratch).await {
+                    conv = new_conv;
+                }
+            }
+            Cmd::RefreshAll => {
+                refresh_inst(&buddy).await;
+                refresh_files(&buddy).await;
+                if let Some(new_conv) = refresh_conv(&buddy, &conv_name, scratch).await {
+                    conv = new_conv;
+                }
+            }
+            Cmd::Invalid(msg) => println!("{} {msg}", ico_err()),
+        }
+    }
+
+
+    if show_status() {
+        println!("->> buddy {} - conv {}", buddy.name(), conv.redacted());
+    }
+
+    if args.scratch && !args.keep_scratch {
+        buddy.delete_conv(conv).await?;
+        if show_status() {
+            println!("{} Scratch conversation deleted", ico_check());
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads the persisted `conv_name` conversation (created anew when `recreate`),
+/// or creates a new one in scratch mode.
+async fn load_conv(buddy: &Buddy, conv_name: &str, scratch: bool, recreate: bool) -> Result<Conv> {
+    if scratch {
+        buddy.create_scratch_conv().await
+    } else {
+        buddy.load_or_create_conv(conv_name, recreate).await
+    }
+}
+
+/// Uploads the instructions again (`/ri`).
+async fn refresh_inst(buddy: &Buddy) {
+    match buddy.upload_instructions().await {
+        Ok(true) => (),
+        Ok(false) => println!("{} No instructions to upload", ico_err()),
+        Err(err) => println!("{} Can't upload the instructions: {err}", ico_err()),
+    }
+}
+
+/// Rebundles and uploads all the bundles again (`/rf`), the failed ones are reported by the upload.
+async fn refresh_files(buddy: &Buddy) {
+    match buddy.upload_files(true).await {
+        Ok(summary) => {
+            if summary.failed.is_empty() && show_status() {
+                println!("{} Files refreshed ({} bundles uploaded)", ico_check(), summary.uploaded);
+            }
+        }
+        Err(err) => println!("{} Can't upload the files: {err}", ico_err()),
+    }
+}
+
+/// Starts the `conv_name` conversation over (`/rc`), returns `None` when it could not be created.
+async fn refresh_conv(buddy: &Buddy, conv_name: &str, scratch: bool) -> Option<Conv> {
+    let res = if scratch {
+        buddy.create_scratch_conv().await
+    } else {
+        buddy.load_or_create_conv(conv_name, true).await
+    };
+    match res {
+        Ok(conv) => Some(conv),
+        Err(err) => {
+            println!("{} Can't create the conversation: {err}", ico_err());
+            None
         }
     }
+}
 
+/// Prints the last exchanges of the conversation, to resume where it was left.
+async fn print_last_exchanges(buddy: &Buddy, conv: &Conv) -> Result<()> {
+    let msgs = buddy.last_exchanges(conv, RESUME_EXCHANGES).await?;
+    if msgs.is_empty() {
+        return Ok(());
+    }
 
-    println!("->> buddy {} - conv {conv:?}", buddy.name());
+    println!("\n{}", text_title("Where you left off"));
+    for msg in msgs {
+        let chars = msg.content.chars().count();
+        let content = if chars > RESUME_MSG_CHARS {
+            let head: String = msg.content.chars().take(RESUME_MSG_CHARS).collect();
+            format!("{head}... ({chars} chars)")
+        } else {
+            msg.content
+        };
+        println!("\n{}:\n{}", msg.role, wrap_text(&content, 80));
+    }
 
     Ok(())
 }
+
+/// Builds the `/explain` and `/start` message, the framing prompt followed by the file content inline.
+///
+/// For long files, asks whether to send only the first lines. Returns `None` when cancelled.
+fn file_msg(file: &Path, prompt: &str) -> Result<Option<String>> {
+    let content = read_to_string(file)?;
+    let line_count = content.lines().count();
+
+    let content = if line_count > EXPLAIN_MAX_LINES {
+        println!("{} '{}' has {line_count} lines", ico_err(), file.display());
+        let items = [
+            format!("Send the first {EXPLAIN_MAX_LINES} lines"),
+            "Send the whole file".to_string(),
+            "Cancel".to_string(),
+        ];
+        match select("What to send", &items)? {
+            0 => content.lines().take(EXPLAIN_MAX_LINES).collect::<Vec<_>>().join("\n"),
+            1 => content,
+            _ => return Ok(None),
+        }
+    } else {
+        content
+    };
+
+    Ok(Some(format!(
+        "{prompt}\n\nFile: {}\n```{}\n{content}\n```",
+        file.display(),
+        file.x_extension()
+    )))
+}
+
+fn print_session_expired(session_start: Instant) {
+    println!(
+        "\n{} Session time budget reached ({}s), exiting",
+        ico_err(),
+        session_start.elapsed().as_secs()
+    );
+}
+
+/// Prints the resolved config, bundle plan, instructions, and live assistant state of the buddy.
+///
+/// NOTE: Read-only mode, any create/modify/delete/upload call errors instead (see `set_read_only`).
+async fn inspect(dir: &Path, profile: Option<&str>) -> Result<()> {
+    set_read_only(true);
+    let buddy = Buddy::init_form_dir(dir, false, profile).await?;
+
+    println!("\n{}", text_title("Config"));
+    println!("{}", buddy.config_toml()?);
+
+    println!("{}", text_title("Bundle plan"));
+    for plan in buddy.bundle_plan()? {
+        println!("- {} -> {} ({} files)", plan.name, plan.file_name, plan.src_files.len());
+        if show_details() {
+            plan.src_files.iter().for_each(|file| println!("    {}", file.display()));
+        }
+    }
+
+    println!("\n{}", text_title("Instructions"));
+    match buddy.instructions()? {
+        Some(instructions) => {
+            let chars = instructions.chars().count();
+            if chars > CONTEXT_INSTRUCTIONS_CHARS {
+                let head: String = instructions.chars().take(CONTEXT_INSTRUCTIONS_CHARS).collect();
+                println!("{head}\n... ({chars} chars)");
+            } else {
+                println!("{instructions}");
+            }
+        }
+        None => println!("(no instructions file)"),
+    }
+
+    println!();
+    print_info(&buddy).await;
+
+    println!("\n{}", text_title("Bundles"));
+    for (bundle_name, status) in buddy.status().await? {
+        let ico = match status {
+            BundleStatus::UpToDate => ico_check(),
+            _ => ico_err(),
+        };
+        println!("{ico} {bundle_name}: {status}");
+    }
+
+    Ok(())
+}
+
+/// Prints the overview of the live assistant.
+async fn print_info(buddy: &Buddy) {
+    match buddy.info().await {
+        Ok(info) => {
+            if show_status() {
+                println!("{}", text_title("Assistant"));
+            }
+            println!("name:         {}", info.name);
+            println!("model:        {}", info.model);
+            println!("tools:        {}", info.tools.join(", "));
+            println!("instructions: {} chars", info.instructions_chars);
+            match info.file_count {
+                Ok(count) => println!("files:        {count}"),
+                Err(err) => println!("files:        {} {err}", ico_err()),
+            }
+        }
+        Err(err) => println!("{} Can't get the assistant info: {err}", ico_err()),
+    }
+}
+
+fn discover_workspace(root: &Path, profile: Option<&str>) -> Result<Workspace> {
+    let workspace = Workspace::discover(root, profile)?;
+    if workspace.buddy_dirs().is_empty() {
+        println!("{} No buddy found under '{}'", ico_err(), root.display());
+    } else if show_status() {
+        println!("{} {} buddies found", ico_check(), workspace.buddy_dirs().len());
+    }
+
+    Ok(workspace)
+}
+
+/// Lists or deletes the assistants of the account (`assistants list|delete <name>`), without a buddy.
+///
+/// NOTE: The API key is from the `profile` or the `OPENAI_API_KEY` env variable (no buddy `.env`).
+async fn manage_assistants(action: &AsstAction, profile: Option<&str>) -> Result<()> {
+    let oac = new_oa_client(ClientOptions {
+        profile: profile.map(String::from),
+        trace: show_trace(),
+        ..Default::default()
+    })?;
+
+    match action {
+        AsstAction::List => {
+            for (asst_id, name) in asst::list_assistants(&oac).await? {
+                println!("{asst_id} - {name}");
+            }
+        }
+        AsstAction::Delete { name } => {
+            let asst_obj = asst::first_by_name(&oac, name)
+                .await?
+                .ok_or_else(|| format!("No assistant named '{name}'"))?;
+            let asst_id: AsstId = asst_obj.id.into();
+            asst::delete(&oac, &asst_id, false).await?;
+            println!("{} Assistant '{name}' deleted - {}", ico_deleted_ok(), asst_id.redacted());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the bundle statuses of each buddy of the workspace.
+async fn status_all(root: &Path, profile: Option<&str>) -> Result<()> {
+    let workspace = discover_workspace(root, profile)?;
+    for (dir, res) in workspace.status_all().await {
+        println!("\n{}", text_title(&dir.display().to_string()));
+        match res {
+            Ok(statuses) => {
+                for (bundle_name, status) in statuses {
+                    let ico = match status {
+                        BundleStatus::UpToDate => ico_check(),
+                        _ => ico_err(),
+                    };
+                    println!("{ico} {bundle_name}: {status}");
+                }
+            }
+            Err(err) => println!("{} {err}", ico_err()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Refreshes each buddy of the workspace, and prints a line per buddy.
+async fn refresh_all(root: &Path, profile: Option<&str>) -> Result<()> {
+    let workspace = discover_workspace(root, profile)?;
+    for (dir, res) in workspace.refresh_all().await {
+        match res {
+            Ok(summary) if summary.failed.is_empty() => {
+                println!("{} {}: {} bundles uploaded", ico_check(), dir.display(), summary.uploaded)
+            }
+            Ok(summary) => println!(
+                "{} {}: {} bundles failed ({})",
+                ico_err(),
+                dir.display(),
+                summary.failed.len(),
+                summary.failed.join(", ")
+            ),
+            Err(err) => println!("{} {}: {err}", ico_err(), dir.display()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the message and prints the answer.
+async fn chat(buddy: &Buddy, conv: &mut Conv, msg: &str, model: Option<&str>) -> Result<()> {
+    let res = buddy.chat(conv, msg, model).await;
+    print_answer(buddy, res).await
+}
+
+/// Prints the answer (paged).
+///
+/// A run cancelled at the session timeout is not an error, the loop exits at the next prompt.
+/// Nor is a run cancelled on Ctrl-C, back to the prompt.
+async fn print_answer(buddy: &Buddy, res: Result<AssistantMessage>) -> Result<()> {
+    let asst_msg = match res {
+        Ok(asst_msg) => asst_msg,
+        Err(err @ Error::RunCancelled) => {
+            println!("{} {err}", ico_err());
+            return Ok(());
+        }
+        Err(err) if buddy.session_expired() => {
+            println!("{} {err}", ico_err());
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+    print_answer_text(&asst_msg.text)?;
+    if show_answers() {
+        for image in asst_msg.images.iter() {
+            println!("  image: {}", image.display());
+        }
+    }
+    if let Some(retrieval) = &asst_msg.retrieval {
+        print_retrieval(retrieval);
+    }
+    print_sources(buddy, &asst_msg.citations).await;
+
+    Ok(())
+}
+
+/// Prints the retrieval diagnostic of the answer (verbose).
+fn print_retrieval(retrieval: &RetrievalInfo) {
+    let hint = format!(
+        "(retrieval: {} calls, {} citations)",
+        retrieval.calls, retrieval.citations
+    );
+    println!("{}", text_dim(hint));
+}
+
+/// Prints the token usage of the session by model, with the estimated cost when priced.
+fn print_tokens(buddy: &Buddy) {
+    let usages = buddy.session_usage();
+    if usages.is_empty() {
+        println!("{} No runs with a token usage in this session", ico_check());
+        return;
+    }
+
+    for model_usage in usages.iter() {
+        let usage = &model_usage.usage;
+        let cost = match model_usage.cost {
+            Some(cost) => format!("~${cost:.4}"),
+            None => "no price".to_string(),
+        };
+        println!(
+            "{}: {} prompt + {} completion = {} tokens ({cost})",
+            model_usage.model, usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+        );
+    }
+    if usages.len() > 1 {
+        let total: u32 = usages.iter().map(|u| u.usage.total_tokens).sum();
+        let cost: f64 = usages.iter().filter_map(|u| u.cost).sum();
+        println!("{}", text_dim(format!("total: {total} tokens (~${cost:.4} priced)")));
+    }
+}
+
+/// Prints the `Sources:` footer, the names of the cited files, and their quotes in details
+/// (none without citations).
+async fn print_sources(buddy: &Buddy, citations: &[Citation]) {
+    if citations.is_empty() || !show_answers() {
+        return;
+    }
+    match buddy.cited_files(citations).await {
+        Ok(files) => {
+            println!("{}", text_dim("Sources:".to_string()));
+            for (name, quotes) in files {
+                println!("{}", text_dim(format!("  - {name}")));
+                if show_details() {
+                    for quote in quotes {
+                        println!("{}", text_dim(format!("      \"{quote}\"")));
+                    }
+                }
+            }
+        }
+        Err(err) => println!("{} Can't resolve the cited files: {err}", ico_err()),
+    }
+}
+
+/// Sends the message, and prints the answer as it is generated.
+///
+/// Once complete, the answer is reprinted wrapped when it fits on the screen
+/// (the streamed text is kept as is otherwise). Not streamed when the output is not a terminal.
+async fn chat_stream(buddy: &Buddy, conv: &mut Conv, msg: &str) -> Result<()> {
+    let active_run = ActiveRun::start();
+    let mut deltas = match buddy.chat_stream(conv, msg).await {
+        Ok(deltas) => Box::pin(deltas),
+        Err(err) if buddy.session_expired() => {
+            println!("{} {err}", ico_err());
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    let term = Term::stdout();
+    let live = term.is_term() && show_answers();
+    let prefix = if show_status() { format!("{} ", ico_res()) } else { String::new() };
+    if live {
+        term.write_str(&prefix)?;
+    }
+    let mut text = String::new();
+    let mut run_id = None;
+    loop {
+        tokio::select! {
+            delta = deltas.next() => match delta {
+                None => break,
+                Some(Ok(RunDelta::Text(delta))) => {
+                    if live {
+                        term.write_str(&delta)?;
+                    }
+                    text.push_str(&delta);
+                }
+                Some(Ok(RunDelta::Completed { run_id: id, truncated, .. })) => {
+                    // Like the blocking chat, for the token limits.
+                    if truncated {
+                        if live {
+                            term.write_str(" [truncated]")?;
+                        }
+                        text.push_str(" [truncated]");
+                    }
+                    run_id = Some(id);
+                }
+                // Cancelled at the session timeout, the loop exits at the next prompt.
+                Some(Err(err)) if buddy.session_expired() => {
+                    if live {
+                        term.write_line("")?;
+                    }
+                    println!("{} {err}", ico_err());
+                    return Ok(());
+                }
+                Some(Err(err)) => return Err(err),
+            },
+            _ = active_run.interrupted() => {
+                drop(deltas);
+                if live {
+                    term.write_line("")?;
+                }
+                // Best-effort, the run ends on its own otherwise.
+                let _ = buddy.cancel_run(conv).await;
+                println!("{} {}", ico_err(), Error::RunCancelled);
+                return Ok(());
+            }
+        }
+    }
+    drop(active_run);
+    // Best-effort, keeps the local history up to date (the blocking chat does it too).
+    let _ = buddy.history_entries(conv).await;
+    // Best-effort, the deltas do not have the annotations.
+    let citations = buddy.last_answer_citations(conv).await.unwrap_or_default();
+    // Best-effort, only a diagnostic (like the blocking chat).
+    let retrieval = match (&run_id, show_details()) {
+        (Some(run_id), true) => buddy.run_retrieval(conv, run_id, citations.len()).await.ok(),
+        _ => None,
+    };
+
+    if live {
+        term.write_line("")?;
+        let (rows, cols) = term.size();
+        let cols = (cols as usize).max(1);
+        let lines: usize = format!("{prefix}{text}")
+            .split('\n')
+            .map(|line| measure_text_width(line).div_ceil(cols).max(1))
+            .sum();
+        if lines < rows as usize {
+            term.clear_last_lines(lines)?;
+            print_answer_text(&text)?;
+        }
+    } else {
+        print_answer_text(&text)?;
+    }
+    if let Some(retrieval) = &retrieval {
+        print_retrieval(retrieval);
+    }
+    print_sources(buddy, &citations).await;
+
+    Ok(())
+}
+
+/// Prints the answer text wrapped, and rendered unless raw (paged).
+fn print_answer_text(text: &str) -> Result<()> {
+    let res = wrap_text(text, 80);
+    if show_status() {
+        let res = if is_raw() { text_res(res).to_string() } else { render_markdown(&res) };
+        page(&format!("{} {res}", ico_res()))?;
+    } else if show_answers() {
+        let res = if is_raw() { res } else { render_markdown(&res) };
+        page(&res)?;
+    }
+
+    Ok(())
+}
+
+/// Starts watching the sources for the auto refresh (`--watch`, `/watch`).
+fn start_watch(buddy: &Buddy) -> Result<(RecommendedWatcher, UnboundedReceiver<Vec<PathBuf>>)> {
+    let watch = buddy.watch(WATCH_DEBOUNCE)?;
+    if show_status() {
+        println!("{} Watching the sources for changes", ico_check());
+    }
+    Ok(watch)
+}
+
+/// Waits for the next input of the source, and when watching, auto refreshes the buddy
+/// on source changes meanwhile.
+async fn next_input(
+    buddy: &Buddy,
+    source: &mut impl PromptSource,
+    mut changes: Option<&mut UnboundedReceiver<Vec<PathBuf>>>,
+) -> Result<NextInput> {
+    let deadline = buddy.session_deadline();
+    let input = source.next();
+    tokio::pin!(input);
+    let expired = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(expired);
+    loop {
+        tokio::select! {
+            input = &mut input => return Ok(input?.map(NextInput::Input).unwrap_or(NextInput::Ended)),
+            _ = &mut expired => return Ok(NextInput::Expired),
+            Some(changed) = async { changes.as_mut()?.recv().await } => {
+                if let Err(err) = buddy.refresh_changed(&changed).await {
+                    println!("{} Auto refresh failed: {err}", ico_err());
+                }
+            }
+        }
+    }
+}
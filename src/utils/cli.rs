@@ -1,24 +1,366 @@
-use console::{Style, style, StyledObject};
-use dialoguer::{Input, theme::ColorfulTheme};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use console::{Color, Key, Style, Term, style, StyledObject};
+use dialoguer::{Input, Select, theme::{ColorfulTheme, SimpleTheme, Theme as PromptTheme}};
 
 use crate::Result;
 
 
+// region: --- Verbosity
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors.
+    Silent = 0,
+    /// Answers and errors, no decorative status lines.
+    Quiet = 1,
+    Normal = 2,
+    /// Normal, plus the per-file upload lines and diagnostic details (e.g., retrieval use), `-v`.
+    Verbose = 3,
+    /// Verbose, plus the timings (uploads, runs, init), `-vv`.
+    Timings = 4,
+    /// Timings, plus a summary line of each API request, `-vvv`.
+    Trace = 5,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Silent,
+        1 => Verbosity::Quiet,
+        2 => Verbosity::Normal,
+        3 => Verbosity::Verbose,
+        4 => Verbosity::Timings,
+        _ => Verbosity::Trace,
+    }
+}
+
+/// Returns true when the decorative status lines (icons, "loaded", "uploaded", ...) should be shown.
+pub fn show_status() -> bool {
+    verbosity() >= Verbosity::Normal
+}
+
+/// Returns true when the diagnostic details should be shown.
+pub fn show_details() -> bool {
+    verbosity() >= Verbosity::Verbose
+}
+
+/// Returns true when the timings should be shown.
+pub fn show_timings() -> bool {
+    verbosity() >= Verbosity::Timings
+}
+
+/// Returns true when the API requests should be traced (see `ClientOptions::trace`).
+pub fn show_trace() -> bool {
+    verbosity() >= Verbosity::Trace
+}
+
+/// Returns true when the answers should be shown.
+pub fn show_answers() -> bool {
+    verbosity() >= Verbosity::Quiet
+}
+
+// endregion: --- Verbosity
+
+
+// region: --- Wrap
+
+static NO_WRAP: AtomicBool = AtomicBool::new(false);
+
+/// Disables the wrapping of the answers (e.g., piped output, soft-wrap terminal).
+pub fn set_no_wrap(no_wrap: bool) {
+    NO_WRAP.store(no_wrap, Ordering::Relaxed);
+}
+
+/// Wraps the markdown prose at `width`, but leaves the fenced code blocks as is
+/// (indentation and long lines kept). No wrap at all when disabled with `set_no_wrap`.
+pub fn wrap_text(text: &str, width: usize) -> String {
+    if NO_WRAP.load(Ordering::Relaxed) {
+        return text.to_string();
+    }
+
+    let mut in_code = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            lines.push(line.to_string());
+        } else if in_code {
+            lines.push(line.to_string());
+        } else {
+            lines.extend(textwrap::wrap(line, width).into_iter().map(|l| l.into_owned()));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// endregion: --- Wrap
+
+
+// region: --- Markdown
+
+static RAW: AtomicBool = AtomicBool::new(false);
+
+/// Keywords highlighted in the code blocks, whatever the language.
+const CODE_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "class", "const", "continue", "def", "else", "enum", "export", "fn", "for",
+    "from", "func", "function", "if", "impl", "import", "in", "let", "loop", "match", "mod", "mut", "pub",
+    "return", "self", "static", "struct", "trait", "type", "use", "var", "where", "while",
+];
+
+/// Disables the markdown rendering of the answers, printed as the raw text (`--raw`).
+pub fn set_raw(raw: bool) {
+    RAW.store(raw, Ordering::Relaxed);
+}
+
+pub fn is_raw() -> bool {
+    RAW.load(Ordering::Relaxed)
+}
+
+/// Renders the (already wrapped) markdown for the terminal, the fenced code blocks indented
+/// and highlighted, the headers as titles, and the bold, italic, and inline code styled.
+///
+/// NOTE: Line based, an emphasis spanning lines stays as is.
+///       Without colors (e.g., piped), the markdown is kept as is.
+pub fn render_markdown(text: &str) -> String {
+    if !console::colors_enabled() {
+        return text.to_string();
+    }
+
+    let mut in_code = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code = !in_code;
+            let lang = trimmed.trim_start_matches(['`', '~']).trim();
+            if in_code && !lang.is_empty() {
+                lines.push(format!("    {}", style(lang).dim().italic()));
+            }
+        } else if in_code {
+            lines.push(format!("    {}", render_code_line(line)));
+        } else if let Some(title) = md_header(trimmed) {
+            lines.push(style(render_inline(title)).fg(theme().accent).bold().to_string());
+        } else {
+            lines.push(render_inline(line));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// Returns the title of a `#` to `######` header line.
+fn md_header(line: &str) -> Option<&str> {
+    let title = line.trim_start_matches('#');
+    let level = line.len() - title.len();
+    ((1..=6).contains(&level) && title.starts_with(' ')).then(|| title.trim())
+}
+
+/// Styles the `**bold**`, `*italic*` (or `_italic_`), and `` `code` `` spans of the line.
+fn render_inline(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find(['*', '_', '`']) {
+        let (before, from) = rest.split_at(start);
+        let marker = if from.starts_with("**") {
+            "**"
+        } else if from.starts_with("__") {
+            "__"
+        } else {
+            &from[..1]
+        };
+        let inner = &from[marker.len()..];
+        // A closing marker, and a non blank span (e.g., not `a * b`).
+        let span = inner
+            .find(marker)
+            .map(|end| &inner[..end])
+            .filter(|span| !span.is_empty() && !span.starts_with(' ') && !span.ends_with(' '));
+        // No intra word `_` emphasis (e.g., `snake_case`).
+        let intra_word = marker.starts_with('_') && before.chars().last().is_some_and(char::is_alphanumeric);
+        out.push_str(before);
+        match span {
+            Some(span) if !intra_word => {
+                let styled = match marker {
+                    "`" => style(span).fg(theme().accent),
+                    "**" | "__" => style(span).bold(),
+                    _ => style(span).italic(),
+                };
+                out.push_str(&styled.to_string());
+                rest = &inner[span.len() + marker.len()..];
+            }
+            _ => {
+                out.push_str(marker);
+                rest = inner;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+/// Highlights the comments, strings, and keywords of a code line.
+fn render_code_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    if ["//", "#", "--"].iter().any(|c| trimmed.starts_with(c)) {
+        return style(line).dim().to_string();
+    }
+
+    let mut out = String::new();
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '"' || c == '\'' {
+            // String literal, up to the closing (not escaped) quote or the line end.
+            let mut end = line.len();
+            let mut escaped = false;
+            for (i, n) in chars.by_ref() {
+                if n == c && !escaped {
+                    end = i + n.len_utf8();
+                    break;
+                }
+                escaped = n == '\\' && !escaped;
+            }
+            out.push_str(&style(&line[start..end]).fg(theme().success).to_string());
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = start + c.len_utf8();
+            while let Some((i, n)) = chars.peek().copied() {
+                if !(n.is_alphanumeric() || n == '_') {
+                    break;
+                }
+                end = i + n.len_utf8();
+                chars.next();
+            }
+            let word = &line[start..end];
+            if CODE_KEYWORDS.contains(&word) {
+                out.push_str(&style(word).fg(theme().accent).to_string());
+            } else {
+                out.push_str(word);
+            }
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+// endregion: --- Markdown
+
+
+// region: --- Colors
+
+/// Colors of the cli roles, the current palette by default.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    /// Prompts, answers icon, and titles.
+    pub accent: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            accent: Color::Color256(45),
+            success: Color::Green,
+            warning: Color::Yellow,
+            error: Color::Red,
+        }
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Sets the theme for the session (first call wins).
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn theme() -> Theme {
+    THEME.get().copied().unwrap_or_default()
+}
+
+/// Parses a color name (e.g., `cyan`) or a 256 colors index (e.g., `45`).
+pub fn parse_color(color: &str) -> Result<Color> {
+    let color = match color.trim().to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        other => Color::Color256(
+            other
+                .parse()
+                .map_err(|_| format!("Invalid color '{color}' (name or 0-255)"))?,
+        ),
+    };
+
+    Ok(color)
+}
+
+/// Disables the colors (stdout and stderr) when `no_color` or the `NO_COLOR` env variable is set.
+///
+/// NOTE: Already disabled by default when the output is not a terminal.
+///       Without colors, the icons are ASCII (e.g., `[ok]`) and the prompts plain.
+pub fn init_colors(no_color: bool) {
+    if no_color || std::env::var_os("NO_COLOR").is_some() {
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
+}
+
+// endregion: --- Colors
+
+
 // region: --- Prompts
 
 pub fn prompt(text: &str) -> Result<String> {
-    let theme = ColorfulTheme {
-        prompt_style: Style::new().for_stderr().color256(45),
-        prompt_prefix: style("?".to_string()).color256(45).for_stderr(),
-        ..ColorfulTheme::default()
-    };
+    let theme = prompt_theme();
 
-    let input = Input::with_theme(&theme);
+    let input = Input::with_theme(theme.as_ref());
     let res = input.with_prompt(text).interact_text()?;
 
     Ok(res)
 }
 
+/// Interactive pick of one of the `items`, returns the selected index.
+pub fn select(text: &str, items: &[String]) -> Result<usize> {
+    let theme = prompt_theme();
+
+    let idx = Select::with_theme(theme.as_ref())
+        .with_prompt(text)
+        .items(items)
+        .default(0)
+        .interact()?;
+
+    Ok(idx)
+}
+
+fn prompt_theme() -> Box<dyn PromptTheme> {
+    if !console::colors_enabled_stderr() {
+        return Box::new(SimpleTheme);
+    }
+    let accent = theme().accent;
+    Box::new(ColorfulTheme {
+        prompt_style: Style::new().for_stderr().fg(accent),
+        prompt_prefix: style("?".to_string()).fg(accent).for_stderr(),
+        ..ColorfulTheme::default()
+    })
+}
+
 // endregion: --- Prompts
 
 
@@ -26,27 +368,36 @@ pub fn prompt(text: &str) -> Result<String> {
 // region: --- Icons
 
 pub fn ico_res() -> StyledObject<&'static str> {
-	style("➤").color256(45)
+	icon("➤", ">", theme().accent)
 }
 
 pub fn ico_check() -> StyledObject<&'static str> {
-	style("✔").green()
+	icon("✔", "[ok]", theme().success)
 }
 
 pub fn ico_uploading() -> StyledObject<&'static str> {
-	style("↥").yellow()
+	icon("↥", "[..]", theme().warning)
 }
 
 pub fn ico_uploaded() -> StyledObject<&'static str> {
-	style("↥").green()
+	icon("↥", "[up]", theme().success)
 }
 
 pub fn ico_deleted_ok() -> StyledObject<&'static str> {
-	style("⌫").green()
+	icon("⌫", "[del]", theme().success)
 }
 
 pub fn ico_err() -> StyledObject<&'static str> {
-	style("✗").red()
+	icon("✗", "[err]", theme().error)
+}
+
+/// The `fancy` icon in color, or the `ascii` one when the colors are disabled (see `init_colors`).
+fn icon(fancy: &'static str, ascii: &'static str, color: Color) -> StyledObject<&'static str> {
+	if console::colors_enabled() {
+		style(fancy).fg(color)
+	} else {
+		style(ascii)
+	}
 }
 
 
@@ -54,10 +405,136 @@ pub fn ico_err() -> StyledObject<&'static str> {
 
 
 
+// region: --- Progress
+
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// A `<msg> <elapsed>` spinner line, redrawn at each `tick`, and cleared when dropped.
+///
+/// When the output is not a terminal or without colors, a single static line instead.
+pub struct Spinner {
+    term: Term,
+    msg: &'static str,
+    start: Instant,
+    frame: usize,
+    animated: bool,
+}
+
+impl Spinner {
+    pub fn start(msg: &'static str) -> Self {
+        let term = Term::stdout();
+        let animated = term.is_term() && console::colors_enabled();
+        let mut spinner = Self {
+            term,
+            msg,
+            start: Instant::now(),
+            frame: 0,
+            animated,
+        };
+        if animated {
+            spinner.tick();
+        } else {
+            let _ = spinner.term.write_line(msg);
+        }
+
+        spinner
+    }
+
+    /// Redraws the line with the next frame and the elapsed time.
+    pub fn tick(&mut self) {
+        if !self.animated {
+            return;
+        }
+        let frame = SPINNER_FRAMES[self.frame % SPINNER_FRAMES.len()];
+        self.frame += 1;
+        let elapsed = format!("{:.0}s", self.start.elapsed().as_secs_f32());
+        let line = format!("{} {} {}", style(frame).fg(theme().accent), self.msg, style(elapsed).dim());
+        let _ = self.term.clear_line().and_then(|_| self.term.write_str(&line));
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if self.animated {
+            let _ = self.term.clear_line();
+        }
+    }
+}
+
+// endregion: --- Progress
+
+
 // region: --- Text Output
 
 pub fn text_res(text: String) -> StyledObject<String> {
     style(text).bright()
 }
 
+pub fn text_dim(text: String) -> StyledObject<String> {
+    style(text).dim()
+}
+
+/// Formats the unix time as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_utc(ts: u64) -> String {
+    // Civil date from the days since the epoch (Howard Hinnant's algorithm).
+    let days = (ts / 86_400) as i64;
+    let (hour, minute) = (ts % 86_400 / 3_600, ts % 3_600 / 60);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02} UTC")
+}
+
+pub fn text_title(text: &str) -> StyledObject<&str> {
+    style(text).fg(theme().accent).bold().underlined()
+}
+
+/// Prints the text, one screen at a time when it exceeds the terminal height.
+///
+/// `Space` shows the next page, `Enter` the next line, and `q`/`Esc` skips the rest.
+/// When stdout is not a terminal, the text is printed at once.
+pub fn page(text: &str) -> Result<()> {
+    let term = Term::stdout();
+    let page_height = term.size().0.saturating_sub(1).max(1) as usize;
+    let lines: Vec<&str> = text.lines().collect();
+
+    if !term.is_term() || lines.len() <= page_height {
+        term.write_line(text)?;
+        return Ok(());
+    }
+
+    let more = style("-- More -- (space: page, enter: line, q: quit)").dim().to_string();
+    let mut shown = 0;
+    let mut next = page_height;
+    while shown < lines.len() {
+        let end = (shown + next).min(lines.len());
+        for line in &lines[shown..end] {
+            term.write_line(line)?;
+        }
+        shown = end;
+        if shown >= lines.len() {
+            break;
+        }
+
+        term.write_str(&more)?;
+        let key = term.read_key()?;
+        term.clear_line()?;
+        next = match key {
+            Key::Char(' ') => page_height,
+            Key::Enter => 1,
+            Key::Char('q') | Key::Escape => break,
+            _ => 0,
+        };
+    }
+
+    Ok(())
+}
+
 // endregion: --- Text Output
\ No newline at end of file
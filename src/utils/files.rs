@@ -1,38 +1,192 @@
 use std::{
-    fs::{self, File},
-    path::{Path, PathBuf}, io::{BufReader, BufWriter, Write, BufRead}, ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf}, io::{BufReader, BufWriter, Write, BufRead, Read}, ffi::OsStr,
 };
 
 use globset::{Glob, GlobSet, GlobSetBuilder};
+use sha2::{Digest, Sha256};
 use walkdir::WalkDir;
 
+use crate::utils::cli::ico_err;
+use crate::utils::transform::{apply_transforms, FileTransform};
 use crate::Result;
 
 // region: --- Fille Bundler
 
-pub fn bundle_to_file(files: Vec<PathBuf>, dst_file: &Path) -> Result<()> {
-    let mut writer = BufWriter::new(File::create(dst_file)?);
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Options of `bundle_to_file`.
+#[derive(Debug, Default)]
+pub struct BundleOptions {
+    /// Strip trailing whitespace and normalize line endings to LF.
+    pub normalize: bool,
+    /// Split the files bigger than this size (in bytes) into parts, each with its own header.
+    pub chunk_max_bytes: Option<u64>,
+    /// Applied in order to each file content before it is written.
+    pub transforms: Vec<Box<dyn FileTransform>>,
+    /// Max size of the bundle (in bytes).
+    pub max_bytes: Option<u64>,
+    /// Max number of files in the bundle.
+    pub max_files: Option<usize>,
+    /// Leave out the files past the limits with a warning, instead of failing.
+    pub truncate: bool,
+}
 
+/// Bundles the `files` into `dst_file`, each prefixed with its file path header.
+///
+/// When `normalize` is true, trailing whitespace is stripped from each line
+/// and line endings are normalized to LF. Otherwise lines are copied as is.
+///
+/// When `chunk_max_bytes` is set, the bigger files are split into parts
+/// (at blank lines when possible), with a `(part N/M)` header each.
+///
+/// The `transforms` (e.g., redact) apply to the whole file content, before the split.
+///
+/// Binary files (see `is_binary_file`) are skipped with a warning.
+///
+/// When the bundle would exceed `max_bytes` or `max_files`, it fails,
+/// or with `truncate`, the remaining files are left out with a warning.
+pub fn bundle_to_file(files: Vec<PathBuf>, dst_file: &Path, options: &BundleOptions) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(dst_file)?);
+    let total_files = files.len();
+    let mut bundled_bytes: u64 = 0;
+    let mut bundled_files: usize = 0;
 
-    for file in files {
+    for (file_idx, file) in files.into_iter().enumerate() {
         if !file.is_file() {
             return  Err(format!("Connot bundle '{:?}' is not a file.", file).into());
         }
-        let reader = get_reader(&file)?;
+        let file_path = file.to_string_lossy();
+        if is_binary_file(&file)? {
+            println!("{} Skipping binary file '{file_path}'", ico_err());
+            continue;
+        }
 
-        writeln!(writer, "\n// ==== file path: {}\n", file.to_string_lossy())?;
+        let mut buf: Vec<u8> = Vec::new();
+
+        let chunk_max_bytes = options
+            .chunk_max_bytes
+            .filter(|max| file.metadata().map(|m| m.len() > *max).unwrap_or(false));
+
+        if !options.transforms.is_empty() || chunk_max_bytes.is_some() {
+            let content = apply_transforms(&options.transforms, &file, read_to_string(&file)?)?;
+            let lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+            let chunks = match chunk_max_bytes {
+                Some(max_bytes) => chunk_lines(lines, max_bytes),
+                None => vec![lines],
+            };
+            let total = chunks.len();
+            for (idx, chunk) in chunks.iter().enumerate() {
+                if total > 1 {
+                    writeln!(buf, "\n// ==== file path: {} (part {}/{})\n", file_path, idx + 1, total)?;
+                } else {
+                    writeln!(buf, "\n// ==== file path: {}\n", file_path)?;
+                }
+                for line in chunk {
+                    write_line(&mut buf, line, options.normalize)?;
+                }
+                writeln!(buf, "\n\n")?;
+            }
+        } else {
+            let mut reader = get_reader(&file)?;
+            writeln!(buf, "\n// ==== file path: {}\n", file_path)?;
+
+            let mut line = String::new();
+            while reader.read_line(&mut line)? > 0 {
+                write_line(&mut buf, &line, options.normalize)?;
+                line.clear();
+            }
+            writeln!(buf, "\n\n")?;
+        }
 
-        for line in reader.lines() {
-            let line = line?;
-            writeln!(writer, "{}", line)?;
+        let over_limit = if options.max_files.is_some_and(|max| bundled_files >= max) {
+            Some("max_files")
+        } else if options.max_bytes.is_some_and(|max| bundled_bytes + buf.len() as u64 > max) {
+            Some("max_bundle_bytes")
+        } else {
+            None
+        };
+        if let Some(limit) = over_limit {
+            if !options.truncate {
+                return Err(format!(
+                    "Bundle '{}' exceeds {limit} at '{file_path}' ({bundled_files} files, {bundled_bytes} bytes bundled)",
+                    dst_file.display()
+                )
+                .into());
+            }
+            println!(
+                "{} Bundle '{}' truncated at {limit}, {} files left out",
+                ico_err(),
+                dst_file.display(),
+                total_files - file_idx
+            );
+            break;
         }
-        writeln!(writer, "\n\n")?;
+
+        writer.write_all(&buf)?;
+        bundled_bytes += buf.len() as u64;
+        bundled_files += 1;
     }
     writer.flush()?;
 
     Ok(())
 }
 
+/// Whether the file looks binary, a null byte or invalid UTF-8 in its first 8KB.
+fn is_binary_file(file: &Path) -> Result<bool> {
+    let mut head = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    File::open(file)?.take(BINARY_SNIFF_BYTES as u64).read_to_end(&mut head)?;
+    if head.contains(&0) {
+        return Ok(true);
+    }
+
+    // A multi-byte char cut at the end of the head is not an error (`error_len` is `None`).
+    Ok(std::str::from_utf8(&head).is_err_and(|err| err.error_len().is_some()))
+}
+
+fn write_line(writer: &mut impl Write, line: &str, normalize: bool) -> Result<()> {
+    if normalize {
+        writeln!(writer, "{}", line.trim_end())?;
+    } else {
+        write!(writer, "{}", line)?;
+        if !line.ends_with('\n') {
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits the lines into chunks of about `max_bytes` (a single longer line stays whole),
+/// after the last blank line of the chunk when there is one.
+fn chunk_lines(lines: Vec<String>, max_bytes: u64) -> Vec<Vec<String>> {
+    let mut chunks = Vec::new();
+    let mut chunk: Vec<String> = Vec::new();
+    let mut size = 0;
+
+    for line in lines {
+        let len = line.len() as u64;
+        if size + len > max_bytes && !chunk.is_empty() {
+            let split = chunk
+                .iter()
+                .rposition(|l| l.trim().is_empty())
+                .filter(|idx| *idx > 0)
+                .map(|idx| idx + 1)
+                .unwrap_or(chunk.len());
+            let rest = chunk.split_off(split);
+            chunks.push(std::mem::replace(&mut chunk, rest));
+            size = chunk.iter().map(|l| l.len() as u64).sum();
+        }
+        size += len;
+        chunk.push(line);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+
+    chunks
+}
+
 // endregion: --- Fille Bundler
 
 // region: --- File Parser/Writer
@@ -46,6 +200,15 @@ pub fn load_from_toml<T>(file: impl AsRef<Path>) -> Result<T>
         Ok(toml::from_str(&content)?)
     }
 
+pub fn load_from_yaml<T>(file: impl AsRef<Path>) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let content = read_to_string(file.as_ref())?;
+
+    Ok(serde_yaml::from_str(&content)?)
+}
+
 pub fn load_from_json<T>(file: impl AsRef<Path>) -> Result<T>
 where
     T: serde::de::DeserializeOwned, {
@@ -67,6 +230,60 @@ where
     Ok(())
     }
 
+/// Reads the json lines of `file` (empty lines skipped).
+pub fn load_from_jsonl<T>(file: impl AsRef<Path>) -> Result<Vec<T>>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let mut items = Vec::new();
+    for line in get_reader(file.as_ref())?.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            items.push(serde_json::from_str(&line)?);
+        }
+    }
+
+    Ok(items)
+}
+
+/// Writes the `items` as json lines to `file` (created or truncated).
+pub fn save_to_jsonl<T>(file: impl AsRef<Path>, items: &[T]) -> Result<()>
+where
+    T: serde::Serialize,
+{
+    let file = file.as_ref();
+
+    let mut writer = BufWriter::new(
+        File::create(file).map_err(|e| format!("Can not create file '{:?}' : {}", file, e))?,
+    );
+    for item in items {
+        serde_json::to_writer(&mut writer, item)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Appends `data` as a single json line at the end of `file` (created if missing).
+pub fn append_to_jsonl<T>(file: impl AsRef<Path>, data: &T) -> Result<()>
+where
+    T: serde::Serialize,
+{
+    let file = file.as_ref();
+
+    let mut writer = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)
+        .map_err(|e| format!("Can not open file '{:?}' : {}", file, e))?;
+    let mut line = serde_json::to_string(data)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
 // endregion: --- File Parser/Writer
 
 
@@ -82,10 +299,13 @@ pub fn ensure_dir(dir: &Path) -> Result<bool> {
     }
 }
 
+/// Lists the files of `dir` matching the `include_globs`, but not the `exclude_globs`,
+/// nor the `ignore_globs` (e.g., `.buddyignore`, matched relative to `dir`, see `is_ignored`).
 pub fn list_files(
     dir: &Path,
     include_globs: Option<&[&str]>,
     exclude_globs: Option<&[&str]>,
+    ignore_globs: Option<&GlobSet>,
 ) -> Result<Vec<PathBuf>> {
     let base_dir_exclude: GlobSet = base_dir_exclude_globs()?;
 
@@ -104,8 +324,12 @@ pub fn list_files(
     .max_depth(depth)
     .into_iter()
     .filter_entry(|e| 
+        // ignored dirs and files are never listed
+        if is_ignored(ignore_globs, dir, e.path()) {
+            false
+        }
         // if dir check dir exclude
-        if e.file_type().is_dir() {
+        else if e.file_type().is_dir() {
             !base_dir_exclude.is_match(e.path())
         } 
         // else file, we apply the globs
@@ -134,6 +358,67 @@ fn base_dir_exclude_globs() -> Result<GlobSet> {
     get_glob_set(&["**/.git", "**/target"])
 }
 
+/// SHA-256 (hex) of the sorted paths of the `files` with their modified time and size, and the `extra` data.
+///
+/// NOTE: Cheap, the file contents are not read, so a change keeping mtime and size is not detected.
+pub fn hash_files_meta(files: &[PathBuf], extra: &str) -> Result<String> {
+    let mut files: Vec<&PathBuf> = files.iter().collect();
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for file in files {
+        let meta = file.metadata()?;
+        let modified = meta
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update(format!("|{modified}|{}\n", meta.len()).as_bytes());
+    }
+    hasher.update(extra.as_bytes());
+
+    Ok(hasher.finalize().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// The glob set of a gitignore-like file (e.g., `.buddyignore`), `None` when the file does not exist.
+///
+/// Matched against the paths relative to the listed dir (see `list_files`), e.g., the bundle `src_dir`. Blank lines and `#` comments are skipped.
+/// A pattern without `/` matches at any depth, and a matched dir excludes all its content.
+///
+/// NOTE: Negations (`!pattern`) are not supported and skipped.
+pub fn load_ignore_globs(file: &Path) -> Result<Option<GlobSet>> {
+    if !file.is_file() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for line in read_to_string(file)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            continue;
+        }
+        let pattern = line.trim_end_matches('/');
+        let pattern = if pattern.contains('/') {
+            pattern.trim_start_matches('/').to_string()
+        } else {
+            format!("**/{pattern}")
+        };
+        builder.add(Glob::new(&pattern)?);
+        builder.add(Glob::new(&format!("{pattern}/**"))?);
+    }
+
+    Ok(Some(builder.build()?))
+}
+
+/// Whether the `file` is matched by the ignore globs, relative to `dir` (never when not under `dir`).
+pub fn is_ignored(ignore_globs: Option<&GlobSet>, dir: &Path, file: &Path) -> bool {
+    match (ignore_globs, file.strip_prefix(dir)) {
+        (Some(globs), Ok(rel_file)) => globs.is_match(rel_file),
+        _ => false,
+    }
+}
+
 pub fn get_glob_set(globs: &[&str]) -> Result<GlobSet> {
     let mut builder = GlobSetBuilder::new();
     for glob in globs {
@@ -156,6 +441,56 @@ pub fn read_to_string(file: &Path) -> Result<String> {
     Ok(content)
 }
 
+/// Removes the file only if it is inside `dir`.
+///
+/// Both paths are canonicalized first, so `..` segments or symlinks
+/// pointing outside of `dir` are refused.
+pub fn safer_remove_file(file: &Path, dir: &Path) -> Result<()> {
+    let file_canon = file
+        .canonicalize()
+        .map_err(|e| format!("Can not resolve '{}': {}", file.display(), e))?;
+    let dir_canon = dir
+        .canonicalize()
+        .map_err(|e| format!("Can not resolve '{}': {}", dir.display(), e))?;
+
+    if !file_canon.is_file() || !file_canon.starts_with(&dir_canon) {
+        return Err(format!(
+            "Refusing to delete '{}', not a file under '{}'",
+            file_canon.display(),
+            dir_canon.display()
+        )
+        .into());
+    }
+
+    fs::remove_file(&file_canon)?;
+
+    Ok(())
+}
+
+/// Removes the `dir` and all its content, unless `dir` is `keep_dir` or one of its ancestors
+/// (e.g., a data dir misconfigured as `..` or `/`).
+pub fn safer_remove_dir(dir: &Path, keep_dir: &Path) -> Result<()> {
+    let dir_canon = dir
+        .canonicalize()
+        .map_err(|e| format!("Can not resolve '{}': {}", dir.display(), e))?;
+    let keep_canon = keep_dir
+        .canonicalize()
+        .map_err(|e| format!("Can not resolve '{}': {}", keep_dir.display(), e))?;
+
+    if !dir_canon.is_dir() || keep_canon.starts_with(&dir_canon) {
+        return Err(format!(
+            "Refusing to delete '{}', not a dir or contains '{}'",
+            dir_canon.display(),
+            keep_canon.display()
+        )
+        .into());
+    }
+
+    fs::remove_dir_all(&dir_canon)?;
+
+    Ok(())
+}
+
 fn get_reader(file: &Path) -> Result<BufReader<File>> {
     let Ok(file) = File::open(file) else {
         return Err(format!("File not found: {}", file.display()).into());
@@ -3,6 +3,10 @@
 
 pub mod files;
 pub mod cli;
+pub mod watch;
+pub mod template;
+pub mod transform;
+pub mod prompt_source;
 
 
 // endregion --- Modules
\ No newline at end of file
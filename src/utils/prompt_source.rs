@@ -0,0 +1,113 @@
+use std::future::Future;
+use std::io;
+use std::path::Path;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader, Lines};
+
+use crate::utils::cli::{prompt, show_status, text_res};
+use crate::{Error, Result};
+
+// region: --- Prompt Sources
+
+/// Where the session inputs come from (e.g., the terminal, or a file for automation).
+pub trait PromptSource {
+    /// The next input, `None` when there are no more (ends the session).
+    fn next(&mut self) -> impl Future<Output = Result<Option<String>>> + Send;
+}
+
+/// The interactive prompt of the terminal.
+#[derive(Debug, Default)]
+pub struct TtyPrompt;
+
+impl PromptSource for TtyPrompt {
+    async fn next(&mut self) -> Result<Option<String>> {
+        // NOTE: On a blocking thread, so the session can still react meanwhile (e.g., watch, timeout).
+        let input = tokio::task::spawn_blocking(|| match prompt("Ask away") {
+            // Ctrl-C at the prompt ends the session.
+            Err(Error::Dialoguer(dialoguer::Error::IO(err))) if err.kind() == io::ErrorKind::Interrupted => Ok(None),
+            res => res.map(Some).map_err(|e| e.to_string()),
+        })
+        .await??;
+
+        Ok(input)
+    }
+}
+
+/// One input per line of a reader (e.g., a file, or piped stdin).
+pub struct LinesPrompt {
+    lines: Lines<BufReader<Box<dyn AsyncRead + Unpin + Send>>>,
+}
+
+impl LinesPrompt {
+    /// Lines of the file, or of stdin for `-`.
+    pub async fn open(file: &Path) -> Result<Self> {
+        let reader: Box<dyn AsyncRead + Unpin + Send> = if file == Path::new("-") {
+            Box::new(tokio::io::stdin())
+        } else {
+            let file = tokio::fs::File::open(file)
+                .await
+                .map_err(|err| format!("Can't open the input file '{}': {err}", file.display()))?;
+            Box::new(file)
+        };
+
+        Ok(Self {
+            lines: BufReader::new(reader).lines(),
+        })
+    }
+}
+
+impl PromptSource for LinesPrompt {
+    async fn next(&mut self) -> Result<Option<String>> {
+        let line = self.lines.next_line().await?;
+        // Echoed, as the terminal prompt would show it.
+        if let (Some(line), true) = (&line, show_status()) {
+            println!("{}", text_res(format!("> {line}")));
+        }
+
+        Ok(line)
+    }
+}
+
+/// A single input, then the session ends (one-shot, e.g., for scripts).
+#[derive(Debug)]
+pub struct OncePrompt(Option<String>);
+
+impl OncePrompt {
+    pub fn new(input: impl Into<String>) -> Self {
+        Self(Some(input.into()))
+    }
+
+    /// The whole piped stdin as the input (none when empty).
+    pub async fn stdin() -> Result<Self> {
+        let mut input = String::new();
+        tokio::io::stdin().read_to_string(&mut input).await?;
+        let input = input.trim();
+
+        Ok(Self((!input.is_empty()).then(|| input.to_string())))
+    }
+}
+
+impl PromptSource for OncePrompt {
+    async fn next(&mut self) -> Result<Option<String>> {
+        Ok(self.0.take())
+    }
+}
+
+/// The prompt source chosen at launch.
+pub enum AnyPrompt {
+    Tty(TtyPrompt),
+    Lines(LinesPrompt),
+    Once(OncePrompt),
+}
+
+impl PromptSource for AnyPrompt {
+    async fn next(&mut self) -> Result<Option<String>> {
+        match self {
+            Self::Tty(source) => source.next().await,
+            Self::Lines(source) => source.next().await,
+            Self::Once(source) => source.next().await,
+        }
+    }
+}
+
+// endregion: --- Prompt Sources
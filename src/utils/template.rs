@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::files::read_to_string;
+use crate::Result;
+
+// region: --- Template
+
+/// Loads the `file` template and replaces its `{{var}}` placeholders with the `vars` values.
+///
+/// Errors when a placeholder has no value.
+pub fn render_template_file(file: &Path, vars: &HashMap<String, String>) -> Result<String> {
+	let template = read_to_string(file)?;
+	render_template(&template, vars)
+}
+
+pub fn render_template(template: &str, vars: &HashMap<String, String>) -> Result<String> {
+	let mut res = String::with_capacity(template.len());
+	let mut missing: Vec<&str> = Vec::new();
+
+	let mut rest = template;
+	while let Some(start) = rest.find("{{") {
+		let Some(len) = rest[start + 2..].find("}}") else {
+			break;
+		};
+		let name = rest[start + 2..start + 2 + len].trim();
+		res.push_str(&rest[..start]);
+		match vars.get(name) {
+			Some(value) => res.push_str(value),
+			None => {
+				if !missing.contains(&name) {
+					missing.push(name);
+				}
+			}
+		}
+		rest = &rest[start + 2 + len + 2..];
+	}
+	res.push_str(rest);
+
+	if missing.is_empty() {
+		Ok(res)
+	} else {
+		Err(format!("Unfilled template placeholders: {}", missing.join(", ")).into())
+	}
+}
+
+/// Parses the `key=value` args into the template vars.
+pub fn parse_template_vars<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<HashMap<String, String>> {
+	args.into_iter()
+		.map(|arg| match arg.split_once('=') {
+			Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+			_ => Err(format!("Invalid template var '{arg}' (expected key=value)").into()),
+		})
+		.collect()
+}
+
+// endregion: --- Template
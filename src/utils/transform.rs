@@ -0,0 +1,113 @@
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use regex::Regex;
+
+use crate::Result;
+
+// region: --- Transforms
+
+/// A transform of the file content before it is bundled (e.g., redact secrets, strip comments).
+pub trait FileTransform: Debug {
+	fn transform(&self, file: &Path, content: String) -> Result<String>;
+}
+
+/// Applies the `transforms` in order (identity when none).
+pub fn apply_transforms(transforms: &[Box<dyn FileTransform>], file: &Path, content: String) -> Result<String> {
+	transforms
+		.iter()
+		.try_fold(content, |content, transform| transform.transform(file, content))
+}
+
+/// Replaces the matches of the patterns (e.g., api keys) with the `replacement`.
+#[derive(Debug)]
+pub struct Redact {
+	patterns: Vec<Regex>,
+	replacement: String,
+}
+
+impl Redact {
+	pub fn new(patterns: &[String], replacement: Option<&str>) -> Result<Self> {
+		let patterns = patterns
+			.iter()
+			.map(|p| Regex::new(p).map_err(|err| format!("Invalid redact pattern '{p}': {err}")))
+			.collect::<std::result::Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			patterns,
+			replacement: replacement.unwrap_or("[REDACTED]").to_string(),
+		})
+	}
+}
+
+impl FileTransform for Redact {
+	fn transform(&self, _file: &Path, content: String) -> Result<String> {
+		let content = self.patterns.iter().fold(content, |content, pattern| {
+			pattern.replace_all(&content, self.replacement.as_str()).into_owned()
+		});
+
+		Ok(content)
+	}
+}
+
+/// Pipes the content through an external command (content on stdin, result on stdout).
+///
+/// As a transform, the file path is passed in the `BUDDY_FILE` env variable.
+/// Also runs the tool commands (see `Buddy::register_tool`).
+#[derive(Debug)]
+pub struct ExternalCommand {
+	program: String,
+	args: Vec<String>,
+}
+
+impl ExternalCommand {
+	pub fn new(command: &[String]) -> Result<Self> {
+		let Some((program, args)) = command.split_first() else {
+			return Err("Empty command".into());
+		};
+
+		Ok(Self {
+			program: program.to_string(),
+			args: args.to_vec(),
+		})
+	}
+}
+
+impl ExternalCommand {
+	/// Runs the command with the `input` on its stdin and the `env` variable, returns its stdout.
+	pub fn run(&self, input: &str, env: (&str, &OsStr)) -> Result<String> {
+		let mut child = Command::new(&self.program)
+			.args(&self.args)
+			.env(env.0, env.1)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::inherit())
+			.spawn()
+			.map_err(|err| format!("Can't run command '{}': {err}", self.program))?;
+
+		// Write stdin while reading stdout, so a big input can not deadlock.
+		let mut stdin = child.stdin.take().ok_or("Command stdin not piped")?;
+		let output = std::thread::scope(|scope| {
+			scope.spawn(move || stdin.write_all(input.as_bytes()));
+			child.wait_with_output()
+		})?;
+
+		if !output.status.success() {
+			return Err(format!("Command '{}' failed ({})", self.program, output.status).into());
+		}
+
+		Ok(String::from_utf8(output.stdout)?)
+	}
+}
+
+impl FileTransform for ExternalCommand {
+	fn transform(&self, file: &Path, content: String) -> Result<String> {
+		self.run(&content, ("BUDDY_FILE", file.as_os_str()))
+			.map_err(|err| format!("Transform of '{}' failed: {err}", file.display()).into())
+	}
+}
+
+// endregion: --- Transforms
@@ -0,0 +1,58 @@
+use std::{path::PathBuf, time::Duration};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::Result;
+
+// region: --- Watcher
+
+/// Watches the `paths` (recursively for dirs) and sends the changed paths
+/// once no new change happened for `debounce`.
+///
+/// NOTE: The returned watcher must be kept alive for the events to flow.
+pub fn watch_paths(
+    paths: &[PathBuf],
+    debounce: Duration,
+) -> Result<(RecommendedWatcher, UnboundedReceiver<Vec<PathBuf>>)> {
+    let (raw_tx, mut raw_rx) = mpsc::unbounded_channel::<Vec<PathBuf>>();
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                let _ = raw_tx.send(event.paths);
+            }
+        }
+    })?;
+
+    for path in paths {
+        let mode = if path.is_dir() {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(path, mode)?;
+    }
+
+    // -- Debounce the raw events
+    tokio::spawn(async move {
+        while let Some(mut changed) = raw_rx.recv().await {
+            while let Ok(Some(more)) = tokio::time::timeout(debounce, raw_rx.recv()).await {
+                changed.extend(more);
+            }
+            changed.sort();
+            changed.dedup();
+            if tx.send(changed).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok((watcher, rx))
+}
+
+// endregion: --- Watcher
@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::{
+    ais::set_read_only,
+    buddy::{has_config, Buddy, BundleStatus, UploadSummary},
+    Result,
+};
+
+// region: --- Types
+
+/// Dirs not searched for buddies (besides the hidden ones, e.g., `.git` or the `.buddy` data dirs).
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+/// The buddy dirs under a root dir (e.g., a monorepo with several `buddy.toml`).
+#[derive(Debug)]
+pub struct Workspace {
+    buddy_dirs: Vec<PathBuf>,
+    /// OpenAI account of all the buddies (overrides their config one).
+    profile: Option<String>,
+}
+
+// endregion: --- Types
+
+impl Workspace {
+    /// Finds all the buddy dirs (with a buddy config) under `root`, sorted by path.
+    pub fn discover(root: impl AsRef<Path>, profile: Option<&str>) -> Result<Self> {
+        let root = root.as_ref();
+        if !root.is_dir() {
+            return Err(format!("Workspace root '{}' is not a dir", root.display()).into());
+        }
+
+        let mut buddy_dirs: Vec<PathBuf> = WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| {
+                let name = e.file_name().to_string_lossy();
+                e.depth() == 0 || !(name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()))
+            })
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir() && has_config(e.path()))
+            .map(|e| e.into_path())
+            .collect();
+        buddy_dirs.sort();
+
+        Ok(Self {
+            buddy_dirs,
+            profile: profile.map(String::from),
+        })
+    }
+
+    pub fn buddy_dirs(&self) -> &[PathBuf] {
+        &self.buddy_dirs
+    }
+
+    /// Loads each buddy (uploading its instructions) and re-uploads all its bundles.
+    ///
+    /// NOTE: A failing buddy does not stop the others, each has its own result.
+    pub async fn refresh_all(&self) -> Vec<(PathBuf, Result<UploadSummary>)> {
+        let mut results = Vec::new();
+        for dir in self.buddy_dirs.iter() {
+            // NOTE: Not synced at init, so the changed bundles are uploaded once.
+            let res = match Buddy::init_form_dir_no_sync(dir, self.profile.as_deref()).await {
+                Ok(buddy) => buddy.sync_asst(true).await,
+                Err(err) => Err(err),
+            };
+            results.push((dir.clone(), res));
+        }
+
+        results
+    }
+
+    /// Returns the bundle statuses of each buddy (see `Buddy::status`).
+    ///
+    /// NOTE: Switches to read-only mode (like inspect), so the assistants are loaded as is,
+    ///       nothing uploaded before the statuses are computed.
+    pub async fn status_all(&self) -> Vec<(PathBuf, Result<Vec<(String, BundleStatus)>>)> {
+        set_read_only(true);
+        let mut results = Vec::new();
+        for dir in self.buddy_dirs.iter() {
+            let res = match Buddy::init_form_dir(dir, false, self.profile.as_deref()).await {
+                Ok(buddy) => buddy.status().await,
+                Err(err) => Err(err),
+            };
+            results.push((dir.clone(), res));
+        }
+
+        results
+    }
+}
@@ -0,0 +1,458 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_ai::utils::files::{
+    append_to_jsonl, bundle_to_file, list_files, load_from_json, load_from_jsonl, load_from_toml,
+    safer_remove_dir, safer_remove_file, save_to_json, save_to_jsonl, BundleOptions,
+};
+use rust_ai::Result;
+use serde::{Deserialize, Serialize};
+use tempfile::tempdir;
+
+// region: --- Helpers
+
+fn write_file(dir: &Path, rel_path: &str, content: impl AsRef<[u8]>) -> PathBuf {
+    let file = dir.join(rel_path);
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(&file, content).unwrap();
+    file
+}
+
+/// The listed files relative to `dir`, sorted.
+fn rel_files(dir: &Path, files: Vec<PathBuf>) -> Vec<String> {
+    let mut files: Vec<String> = files
+        .iter()
+        .map(|f| f.strip_prefix(dir).unwrap().to_string_lossy().replace('\\', "/"))
+        .collect();
+    files.sort();
+    files
+}
+
+// endregion: --- Helpers
+
+// region: --- list_files
+
+#[test]
+fn list_files_include_globs() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    write_file(dir, "main.rs", "fn main() {}");
+    write_file(dir, "README.md", "# Readme");
+    write_file(dir, "sub/mod.rs", "");
+
+    let files = list_files(dir, Some(&["*.rs"]), None, None)?;
+
+    assert_eq!(rel_files(dir, files), ["main.rs"]);
+    Ok(())
+}
+
+#[test]
+fn list_files_exclude_globs() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    write_file(dir, "src/main.rs", "");
+    write_file(dir, "src/gen/schema.rs", "");
+    write_file(dir, "src/lib.rs", "");
+
+    let files = list_files(dir, Some(&["**/*.rs"]), Some(&["**/gen/**"]), None)?;
+
+    assert_eq!(rel_files(dir, files), ["src/lib.rs", "src/main.rs"]);
+    Ok(())
+}
+
+#[test]
+fn list_files_no_globs_is_top_level_only() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    write_file(dir, "a.txt", "");
+    write_file(dir, "sub/b.txt", "");
+
+    let files = list_files(dir, None, None, None)?;
+
+    assert_eq!(rel_files(dir, files), ["a.txt"]);
+    Ok(())
+}
+
+#[test]
+fn list_files_depth_from_double_star() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    write_file(dir, "top.rs", "");
+    write_file(dir, "a/b/c/d/deep.rs", "");
+
+    // -- Without `**`, only the top level is walked.
+    let files = list_files(dir, Some(&["*.rs"]), None, None)?;
+    assert_eq!(rel_files(dir, files), ["top.rs"]);
+
+    // -- With `**` in any of the globs, the nested dirs are walked.
+    let files = list_files(dir, Some(&["*.md", "**/*.rs"]), None, None)?;
+    assert_eq!(rel_files(dir, files), ["a/b/c/d/deep.rs", "top.rs"]);
+    Ok(())
+}
+
+#[test]
+fn list_files_skips_git_and_target_dirs() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    write_file(dir, "src/main.rs", "");
+    write_file(dir, ".git/hooks/pre-commit.rs", "");
+    write_file(dir, "target/debug/build.rs", "");
+
+    let files = list_files(dir, Some(&["**/*.rs"]), None, None)?;
+
+    assert_eq!(rel_files(dir, files), ["src/main.rs"]);
+    Ok(())
+}
+
+#[test]
+fn list_files_rs_globs_with_target_excludes() -> Result<()> {
+    let tmp = tempdir()?;
+    let src_dir = tmp.path();
+    write_file(src_dir, "build.rs", "");
+    write_file(src_dir, "src/main.rs", "");
+    write_file(src_dir, "src/utils/mod.rs", "");
+    write_file(src_dir, "src/utils/README.md", "");
+    write_file(src_dir, "Cargo.toml", "");
+    write_file(src_dir, "target/debug/out.rs", "");
+    write_file(src_dir, "crates/core/target/release/gen.rs", "");
+    write_file(src_dir, "crates/core/src/lib.rs", "");
+
+    let files = list_files(src_dir, Some(&["**/*.rs"]), Some(&["**/target/**"]), None)?;
+
+    assert_eq!(
+        rel_files(src_dir, files),
+        ["build.rs", "crates/core/src/lib.rs", "src/main.rs", "src/utils/mod.rs"]
+    );
+    Ok(())
+}
+
+// endregion: --- list_files
+
+// region: --- bundle_to_file
+
+#[test]
+fn bundle_to_file_headers_and_content() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let main_file = write_file(dir, "main.rs", "fn main() {}\n");
+    let lib_file = write_file(dir, "lib.rs", "pub mod utils;");
+    let bundle = dir.join("bundle.rs");
+
+    bundle_to_file(vec![main_file.clone(), lib_file.clone()], &bundle, &BundleOptions::default())?;
+
+    let content = fs::read_to_string(&bundle)?;
+    let main_header = format!("// ==== file path: {}", main_file.display());
+    let lib_header = format!("// ==== file path: {}", lib_file.display());
+    assert!(content.contains(&main_header));
+    assert!(content.contains(&lib_header));
+    assert!(content.find(&main_header) < content.find(&lib_header));
+    assert!(content.contains("fn main() {}\n"));
+    // -- The last line without a newline gets one.
+    assert!(content.contains("pub mod utils;\n"));
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_normalize() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let file = write_file(dir, "crlf.rs", "let a = 1;   \r\nlet b = 2;\r\n");
+    let bundle = dir.join("bundle.rs");
+
+    let options = BundleOptions {
+        normalize: true,
+        ..Default::default()
+    };
+    bundle_to_file(vec![file], &bundle, &options)?;
+
+    let content = fs::read_to_string(&bundle)?;
+    assert!(content.contains("let a = 1;\nlet b = 2;\n"));
+    assert!(!content.contains('\r'));
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_missing_file_fails() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+
+    let res = bundle_to_file(vec![dir.join("missing.rs")], &dir.join("bundle.rs"), &BundleOptions::default());
+
+    assert!(res.is_err());
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_skips_binary() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let null_file = write_file(dir, "null.bin", b"abc\0def");
+    let invalid_file = write_file(dir, "latin1.txt", b"caf\xe9 au lait");
+    let text_file = write_file(dir, "text.rs", "// text");
+    let bundle = dir.join("bundle.rs");
+
+    bundle_to_file(vec![null_file, invalid_file, text_file], &bundle, &BundleOptions::default())?;
+
+    let content = fs::read_to_string(&bundle)?;
+    assert!(!content.contains("null.bin"));
+    assert!(!content.contains("latin1.txt"));
+    assert!(content.contains("// text"));
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_multi_byte_char_at_sniff_end_is_text() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    // -- "é" (2 bytes) cut by the 8KB sniff, the file is still valid UTF-8.
+    let mut content = "a".repeat(8 * 1024 - 1);
+    content.push_str("é\n");
+    let file = write_file(dir, "utf8.txt", &content);
+    let bundle = dir.join("bundle.rs");
+
+    bundle_to_file(vec![file], &bundle, &BundleOptions::default())?;
+
+    let bundled = fs::read_to_string(&bundle)?;
+    assert!(bundled.contains("utf8.txt"));
+    assert!(bundled.contains("é\n"));
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_empty_file() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let file = write_file(dir, "empty.rs", "");
+    let bundle = dir.join("bundle.rs");
+
+    bundle_to_file(vec![file], &bundle, &BundleOptions::default())?;
+
+    assert!(fs::read_to_string(&bundle)?.contains("empty.rs"));
+    Ok(())
+}
+
+#[test]
+fn bundle_dir_with_png_bundles_only_rs() -> Result<()> {
+    let tmp = tempdir()?;
+    let src_dir = tmp.path().join("src");
+    // -- PNG signature and IHDR chunk start (has null bytes).
+    let png: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D, b'I', b'H', b'D', b'R'];
+    write_file(&src_dir, "logo.png", png);
+    write_file(&src_dir, "main.rs", "fn main() {}\n");
+    let bundle = tmp.path().join("bundle.rs");
+
+    let files = list_files(&src_dir, None, None, None)?;
+    assert_eq!(files.len(), 2);
+    bundle_to_file(files, &bundle, &BundleOptions::default())?;
+
+    let content = fs::read_to_string(&bundle)?;
+    assert_eq!(content.matches("// ==== file path:").count(), 1);
+    assert!(content.contains("main.rs"));
+    assert!(!content.contains("logo.png"));
+    Ok(())
+}
+
+/// Three small files, the limits set to fit only two.
+fn limits_files(dir: &Path) -> Vec<PathBuf> {
+    vec![
+        write_file(dir, "a.rs", "// a\n"),
+        write_file(dir, "b.rs", "// b\n"),
+        write_file(dir, "c.rs", "// c\n"),
+    ]
+}
+
+#[test]
+fn bundle_to_file_limits_error() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let bundle = dir.join("bundle.rs");
+
+    let options = BundleOptions {
+        max_files: Some(2),
+        ..Default::default()
+    };
+    let err = bundle_to_file(limits_files(dir), &bundle, &options).unwrap_err();
+    assert!(err.to_string().contains("max_files"));
+
+    let options = BundleOptions {
+        max_bytes: Some(10),
+        ..Default::default()
+    };
+    let err = bundle_to_file(limits_files(dir), &bundle, &options).unwrap_err();
+    assert!(err.to_string().contains("max_bundle_bytes"));
+    Ok(())
+}
+
+#[test]
+fn bundle_to_file_limits_truncate() -> Result<()> {
+    let tmp = tempdir()?;
+    let dir = tmp.path();
+    let bundle = dir.join("bundle.rs");
+
+    // -- max_files
+    let options = BundleOptions {
+        max_files: Some(2),
+        truncate: true,
+        ..Default::default()
+    };
+    bundle_to_file(limits_files(dir), &bundle, &options)?;
+    let content = fs::read_to_string(&bundle)?;
+    assert!(content.contains("// a") && content.contains("// b"));
+    assert!(!content.contains("// c"));
+
+    // -- max_bytes, room for the first file only
+    let one_file_bytes = {
+        bundle_to_file(vec![dir.join("a.rs")], &bundle, &BundleOptions::default())?;
+        fs::metadata(&bundle)?.len()
+    };
+    let options = BundleOptions {
+        max_bytes: Some(one_file_bytes + 1),
+        truncate: true,
+        ..Default::default()
+    };
+    bundle_to_file(limits_files(dir), &bundle, &options)?;
+    let content = fs::read_to_string(&bundle)?;
+    assert!(content.contains("// a"));
+    assert!(!content.contains("// b") && !content.contains("// c"));
+    assert!(fs::metadata(&bundle)?.len() <= one_file_bytes + 1);
+    Ok(())
+}
+
+// endregion: --- bundle_to_file
+
+// region: --- Load/Save
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Item {
+    name: String,
+    count: u32,
+}
+
+fn item(name: &str, count: u32) -> Item {
+    Item {
+        name: name.to_string(),
+        count,
+    }
+}
+
+#[test]
+fn save_and_load_json() -> Result<()> {
+    let tmp = tempdir()?;
+    let file = tmp.path().join("item.json");
+
+    save_to_json(&file, &item("one", 1))?;
+    let loaded: Item = load_from_json(&file)?;
+
+    assert_eq!(loaded, item("one", 1));
+    Ok(())
+}
+
+#[test]
+fn save_append_and_load_jsonl() -> Result<()> {
+    let tmp = tempdir()?;
+    let file = tmp.path().join("items.jsonl");
+
+    save_to_jsonl(&file, &[item("one", 1), item("two", 2)])?;
+    append_to_jsonl(&file, &item("three", 3))?;
+    // -- Empty lines are skipped.
+    fs::write(&file, format!("{}\n\n", fs::read_to_string(&file)?))?;
+    let loaded: Vec<Item> = load_from_jsonl(&file)?;
+
+    assert_eq!(loaded, [item("one", 1), item("two", 2), item("three", 3)]);
+    Ok(())
+}
+
+#[test]
+fn append_to_jsonl_creates_file() -> Result<()> {
+    let tmp = tempdir()?;
+    let file = tmp.path().join("new.jsonl");
+
+    append_to_jsonl(&file, &item("one", 1))?;
+    let loaded: Vec<Item> = load_from_jsonl(&file)?;
+
+    assert_eq!(loaded, [item("one", 1)]);
+    Ok(())
+}
+
+#[test]
+fn load_from_toml_ok_and_missing() -> Result<()> {
+    let tmp = tempdir()?;
+    let file = write_file(tmp.path(), "item.toml", "name = \"one\"\ncount = 1\n");
+
+    let loaded: Item = load_from_toml(&file)?;
+    assert_eq!(loaded, item("one", 1));
+
+    let missing: Result<Item> = load_from_toml(tmp.path().join("missing.toml"));
+    assert!(missing.is_err());
+    Ok(())
+}
+
+// endregion: --- Load/Save
+
+// region: --- Safer Remove
+
+#[test]
+fn safer_remove_file_inside_dir() -> Result<()> {
+    let tmp = tempdir()?;
+    let data_dir = tmp.path().join("data");
+    let file = write_file(&data_dir, "bundle.rs", "");
+
+    safer_remove_file(&file, &data_dir)?;
+
+    assert!(!file.exists());
+    Ok(())
+}
+
+#[test]
+fn safer_remove_file_outside_dir_is_kept() -> Result<()> {
+    let tmp = tempdir()?;
+    let data_dir = tmp.path().join("data");
+    fs::create_dir_all(&data_dir)?;
+    let outside_file = write_file(tmp.path(), "main.rs", "fn main() {}");
+
+    // -- Directly, and through a `..` segment under the data dir.
+    assert!(safer_remove_file(&outside_file, &data_dir).is_err());
+    assert!(safer_remove_file(&data_dir.join("../main.rs"), &data_dir).is_err());
+
+    assert!(outside_file.exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn safer_remove_file_symlink_outside_dir_is_kept() -> Result<()> {
+    let tmp = tempdir()?;
+    let data_dir = tmp.path().join("data");
+    fs::create_dir_all(&data_dir)?;
+    let outside_file = write_file(tmp.path(), "main.rs", "fn main() {}");
+    let link = data_dir.join("link.rs");
+    std::os::unix::fs::symlink(&outside_file, &link)?;
+
+    assert!(safer_remove_file(&link, &data_dir).is_err());
+
+    assert!(outside_file.exists());
+    Ok(())
+}
+
+#[test]
+fn safer_remove_dir_keeps_data_dir_and_ancestors() -> Result<()> {
+    let tmp = tempdir()?;
+    let data_dir = tmp.path().join("data");
+    let bundles_dir = data_dir.join("bundles");
+    let file = write_file(&bundles_dir, "bundle.rs", "");
+    let outside_file = write_file(tmp.path(), "main.rs", "");
+
+    // -- The data dir itself, or one of its ancestors, is never removed.
+    assert!(safer_remove_dir(&data_dir, &data_dir).is_err());
+    assert!(safer_remove_dir(tmp.path(), &data_dir).is_err());
+    assert!(file.exists());
+    assert!(outside_file.exists());
+
+    // -- A dir under it is.
+    safer_remove_dir(&bundles_dir, &data_dir)?;
+    assert!(!bundles_dir.exists());
+    assert!(outside_file.exists());
+    Ok(())
+}
+
+// endregion: --- Safer Remove